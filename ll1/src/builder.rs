@@ -0,0 +1,319 @@
+use indexmap::IndexMap;
+
+use super::error::GrammarError;
+use super::grammar::SymbolOrder;
+use super::Grammar;
+
+/// A symbol of an alternative built in code
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    Terminal(String),
+    NonTerminal(String),
+    Epsilon,
+}
+
+/// Builds a `Grammar` in code, checking every symbol before it is created:
+///
+/// ```
+/// use ll1::builder::GrammarBuilder;
+/// use ll1::error::GrammarError;
+/// use ll1::Grammar;
+///
+/// fn expressions() -> Result<Grammar, GrammarError> {
+///     GrammarBuilder::new()
+///         .start("E")
+///         .rule("E", |r| r.nt("T").nt("E'"))
+///         .rule("E'", |r| r.t("+").nt("T").nt("E'").or().eps())
+///         .rule("T", |r| r.t("id"))
+///         .build()
+/// }
+///
+/// let grammar = expressions()?;
+/// assert_eq!(grammar.start_symbol, "E");
+/// assert_eq!(grammar.productions.len(), 4);
+/// assert!(grammar.terminals.contains("+"));
+/// assert!(grammar.non_terminals.contains("E'"));
+/// assert!(grammar.is_ll1());
+/// # Ok::<(), GrammarError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GrammarBuilder {
+    start: Option<String>,
+    name: Option<String>,
+    order: SymbolOrder,
+    /// Alternatives of each non-terminal, in the order the rules were added
+    rules: IndexMap<String, Vec<Vec<Symbol>>>,
+}
+
+/// The alternatives of one rule, separated by `or`
+#[derive(Debug, Clone)]
+pub struct RuleBuilder {
+    alternatives: Vec<Vec<Symbol>>,
+}
+
+impl GrammarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the start symbol, which defaults to the first rule's non-terminal
+    pub fn start(mut self, start_symbol: &str) -> Self {
+        self.start = Some(start_symbol.to_string());
+        self
+    }
+
+    /// Names the grammar, like the `%name` directive
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the order of the output, like the `%order` directive
+    pub fn order(mut self, order: SymbolOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Adds the alternatives built by `build` to the rules of `non_terminal`.
+    /// Calling it again for the same non-terminal adds more alternatives.
+    pub fn rule<F>(mut self, non_terminal: &str, build: F) -> Self
+    where
+        F: FnOnce(RuleBuilder) -> RuleBuilder,
+    {
+        let rule = build(RuleBuilder {
+            alternatives: vec![Vec::new()],
+        });
+        self.rules
+            .entry(non_terminal.to_string())
+            .or_default()
+            .extend(rule.alternatives);
+        self
+    }
+
+    /// Checks the rules and creates the grammar.
+    ///
+    /// Fails when there are no rules, when the start symbol or a non-terminal
+    /// used in an alternative has no rule, when a symbol is used as both kinds,
+    /// or when a terminal is empty or spells `ε` or `$`.
+    pub fn build(self) -> Result<Grammar, GrammarError> {
+        let start_symbol = match (&self.start, self.rules.keys().next()) {
+            (Some(start), _) => start.clone(),
+            (None, Some(first)) => first.clone(),
+            (None, None) => return Err(GrammarError::EmptyGrammar),
+        };
+        if !self.rules.contains_key(&start_symbol) {
+            return Err(GrammarError::InvalidStartSymbol {
+                symbol: start_symbol,
+                reason: "has no rule".to_string(),
+                span: None,
+            });
+        }
+
+        let mut grammar = Grammar::new(&start_symbol);
+        grammar.name = self.name.clone();
+        grammar.order = self.order;
+        for non_terminal in self.rules.keys() {
+            grammar.non_terminals.insert(non_terminal.clone());
+        }
+
+        for (non_terminal, alternatives) in &self.rules {
+            for symbol in alternatives.iter().flatten() {
+                match symbol {
+                    Symbol::NonTerminal(symbol) if !self.rules.contains_key(symbol) => {
+                        return Err(GrammarError::UndefinedNonTerminal {
+                            symbol: symbol.clone(),
+                            used_by: non_terminal.clone(),
+                        });
+                    }
+                    Symbol::Terminal(symbol) if self.rules.contains_key(symbol) => {
+                        return Err(GrammarError::KindConflict {
+                            symbol: symbol.clone(),
+                        });
+                    }
+                    Symbol::Terminal(symbol) if symbol.is_empty() => {
+                        return Err(GrammarError::InvalidTerminal {
+                            symbol: symbol.clone(),
+                            reason: "is empty; use eps() for the empty string".to_string(),
+                        });
+                    }
+                    Symbol::Terminal(symbol) if symbol == "ε" || symbol == "$" => {
+                        return Err(GrammarError::InvalidTerminal {
+                            symbol: symbol.clone(),
+                            reason: "is reserved".to_string(),
+                        });
+                    }
+                    Symbol::Terminal(symbol) => grammar.add_terminal(symbol),
+                    _ => {}
+                }
+            }
+        }
+
+        for (non_terminal, alternatives) in &self.rules {
+            for alternative in alternatives {
+                let derivation = alternative
+                    .iter()
+                    .filter_map(|symbol| match symbol {
+                        Symbol::Terminal(symbol) | Symbol::NonTerminal(symbol) => {
+                            Some(symbol.as_str())
+                        }
+                        Symbol::Epsilon => None,
+                    })
+                    .collect::<Vec<_>>();
+                let derivation = if derivation.is_empty() {
+                    vec!["ε"]
+                } else {
+                    derivation
+                };
+                grammar.add_production(non_terminal, derivation);
+            }
+        }
+
+        Ok(grammar)
+    }
+}
+
+impl RuleBuilder {
+    /// Appends a non-terminal to the current alternative
+    pub fn nt(self, non_terminal: &str) -> Self {
+        self.push(Symbol::NonTerminal(non_terminal.to_string()))
+    }
+
+    /// Appends a terminal to the current alternative
+    pub fn t(self, terminal: &str) -> Self {
+        self.push(Symbol::Terminal(terminal.to_string()))
+    }
+
+    /// Marks the current alternative as deriving the empty string
+    pub fn eps(self) -> Self {
+        self.push(Symbol::Epsilon)
+    }
+
+    /// Starts the next alternative
+    pub fn or(mut self) -> Self {
+        self.alternatives.push(Vec::new());
+        self
+    }
+
+    fn push(mut self, symbol: Symbol) -> Self {
+        if let Some(alternative) = self.alternatives.last_mut() {
+            alternative.push(symbol);
+        }
+        self
+    }
+}
+
+/// Whether two strings are equal, usable in constant expressions such as
+/// the checks `grammar!` generates
+#[doc(hidden)]
+pub const fn same_str(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Builds a `Grammar` from rules written in arrow notation:
+///
+/// ```
+/// let grammar = ll1::grammar! {
+///     E    -> T Tail ;
+///     Tail -> "+" T Tail | ;
+///     T    -> "(" E ")" | "id" ;
+/// };
+/// assert_eq!(grammar.start_symbol, "E");
+/// assert!(grammar.is_ll1());
+/// ```
+///
+/// Identifiers are non-terminals and string literals are terminals; the first
+/// rule's non-terminal is the start symbol. An empty alternative derives ε.
+/// Each non-terminal has a single rule. Primes cannot be written in Rust
+/// tokens, so `E'` is spelled `E_` or `Tail` instead.
+///
+/// Every check of `GrammarBuilder::build` happens at compile time, so the
+/// macro never fails at runtime. Using a non-terminal that has no rule does
+/// not compile:
+///
+/// ```compile_fail
+/// let grammar = ll1::grammar! {
+///     E -> T "+" E | T ;
+/// };
+/// ```
+///
+/// nor does a terminal spelled like a non-terminal, or an empty or reserved
+/// terminal:
+///
+/// ```compile_fail
+/// let grammar = ll1::grammar! {
+///     E -> "E" "+" T | T ;
+///     T -> "id" ;
+/// };
+/// ```
+#[macro_export]
+macro_rules! grammar {
+    // Between rules
+    (@rules [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident -> $($rest:tt)*) => {
+        $crate::grammar!(@alts [$($acc)*] [$($def)* $lhs] [$($use)*] [$($lit)*] $lhs [] $($rest)*)
+    };
+    (@rules [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*]) => {{
+        #[allow(dead_code, non_camel_case_types, clippy::upper_case_acronyms)]
+        enum NonTerminals {
+            $($def,)*
+        }
+        $(let _ = NonTerminals::$use;)*
+        const _: () = {
+            let terminals: &[&str] = &[$($lit),*];
+            let non_terminals: &[&str] = &[$(stringify!($def)),*];
+            let mut i = 0;
+            while i < terminals.len() {
+                let terminal = terminals[i];
+                assert!(!terminal.is_empty(), "a terminal is empty; use an empty alternative for ε");
+                assert!(
+                    !$crate::builder::same_str(terminal, "ε") && !$crate::builder::same_str(terminal, "$"),
+                    "a terminal is reserved"
+                );
+                let mut j = 0;
+                while j < non_terminals.len() {
+                    assert!(
+                        !$crate::builder::same_str(terminal, non_terminals[j]),
+                        "a terminal is spelled like a non-terminal"
+                    );
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
+        $crate::builder::GrammarBuilder::new()
+            $($acc)*
+            .build()
+            .expect("grammar! checks its rules at compile time")
+    }};
+
+    // Inside the alternatives of `$lhs`
+    (@alts [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident [$($cur:tt)*] ; $($rest:tt)*) => {
+        $crate::grammar!(@rules [$($acc)* .rule(stringify!($lhs), |r| r $($cur)*)] [$($def)*] [$($use)*] [$($lit)*] $($rest)*)
+    };
+    (@alts [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident [$($cur:tt)*]) => {
+        $crate::grammar!(@rules [$($acc)* .rule(stringify!($lhs), |r| r $($cur)*)] [$($def)*] [$($use)*] [$($lit)*])
+    };
+    (@alts [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident [$($cur:tt)*] | $($rest:tt)*) => {
+        $crate::grammar!(@alts [$($acc)*] [$($def)*] [$($use)*] [$($lit)*] $lhs [$($cur)* .or()] $($rest)*)
+    };
+    (@alts [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident [$($cur:tt)*] $t:literal $($rest:tt)*) => {
+        $crate::grammar!(@alts [$($acc)*] [$($def)*] [$($use)*] [$($lit)* $t] $lhs [$($cur)* .t($t)] $($rest)*)
+    };
+    (@alts [$($acc:tt)*] [$($def:ident)*] [$($use:ident)*] [$($lit:literal)*] $lhs:ident [$($cur:tt)*] $nt:ident $($rest:tt)*) => {
+        $crate::grammar!(@alts [$($acc)*] [$($def)*] [$($use)* $nt] [$($lit)*] $lhs [$($cur)* .nt(stringify!($nt))] $($rest)*)
+    };
+
+    ($($body:tt)+) => {
+        $crate::grammar!(@rules [] [] [] [] $($body)+)
+    };
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::dialect::Dialect;
+use super::format::DEFAULT_WIDTH;
+use super::Grammar;
+
+/// Printed for `ll1 help` and for arguments that are not understood
+pub const USAGE: &str = "\
+Usage:
+    ll1                                 Analyse the built-in grammar and parse standard input
+    ll1 factor <file> [options]         Left-factor a grammar and print the result
+    ll1 ll1ify <file> [options]         Transform a grammar towards LL(1) and log each step
+    ll1 conflicts <file> [options]      List the LL(1) conflicts with a counterexample for each
+    ll1 fmt <file>... [options]         Rewrite grammar files in the canonical layout
+
+Options:
+    --dialect <arrow|unicode|bnf|yacc>  Read the grammar in this dialect instead of detecting it
+    --side-by-side                      Print each original rule next to its rewritten rules
+    --check                             fmt: list the files that are not formatted and fail instead of rewriting them
+    --width <columns>                   fmt: put one alternative per line in rules longer than this (default 80)";
+
+/// A subcommand given on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Prints the usage
+    Help,
+    /// Left-factors the grammar in a file
+    Factor {
+        path: PathBuf,
+        dialect: Option<Dialect>,
+        side_by_side: bool,
+    },
+    /// Runs the LL(1) pipeline on the grammar in a file
+    Ll1ify {
+        path: PathBuf,
+        dialect: Option<Dialect>,
+        side_by_side: bool,
+    },
+    /// Explains the LL(1) conflicts of the grammar in a file
+    Conflicts {
+        path: PathBuf,
+        dialect: Option<Dialect>,
+    },
+    /// Formats grammar files in place, or only checks them
+    Fmt {
+        paths: Vec<PathBuf>,
+        dialect: Option<Dialect>,
+        check: bool,
+        width: usize,
+    },
+}
+
+impl Command {
+    /// Parses the arguments after the program name, or returns `None`
+    /// when there are none
+    pub fn parse(args: &[String]) -> Result<Option<Command>, String> {
+        let Some((name, rest)) = args.split_first() else {
+            return Ok(None);
+        };
+
+        let mut paths = Vec::new();
+        let mut dialect = None;
+        let mut side_by_side = false;
+        let mut check = false;
+        let mut width = DEFAULT_WIDTH;
+        let mut rest = rest.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--dialect" => {
+                    let name = rest
+                        .next()
+                        .ok_or_else(|| "Missing value for --dialect".to_string())?;
+                    dialect = Some(name.parse()?);
+                }
+                "--side-by-side" => side_by_side = true,
+                "--check" => check = true,
+                "--width" => {
+                    let columns = rest
+                        .next()
+                        .ok_or_else(|| "Missing value for --width".to_string())?;
+                    width = columns
+                        .parse()
+                        .map_err(|_| format!("Invalid width '{}'", columns))?;
+                }
+                option if option.starts_with("--") => {
+                    return Err(format!("Unknown option '{}'\n\n{}", option, USAGE));
+                }
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        let path = match paths.as_slice() {
+            [path] => Some(path.clone()),
+            [_, extra, ..] if name != "fmt" => {
+                return Err(format!(
+                    "Unexpected argument '{}'\n\n{}",
+                    extra.display(),
+                    USAGE
+                ))
+            }
+            _ => None,
+        };
+
+        match name.as_str() {
+            "help" | "--help" | "-h" => Ok(Some(Command::Help)),
+            "factor" => Ok(Some(Command::Factor {
+                path: path.ok_or_else(|| format!("Missing grammar file\n\n{}", USAGE))?,
+                dialect,
+                side_by_side,
+            })),
+            "ll1ify" => Ok(Some(Command::Ll1ify {
+                path: path.ok_or_else(|| format!("Missing grammar file\n\n{}", USAGE))?,
+                dialect,
+                side_by_side,
+            })),
+            "conflicts" => Ok(Some(Command::Conflicts {
+                path: path.ok_or_else(|| format!("Missing grammar file\n\n{}", USAGE))?,
+                dialect,
+            })),
+            "fmt" if paths.is_empty() => Err(format!("Missing grammar file\n\n{}", USAGE)),
+            "fmt" => Ok(Some(Command::Fmt {
+                paths,
+                dialect,
+                check,
+                width,
+            })),
+            _ => Err(format!("Unknown command '{}'\n\n{}", name, USAGE)),
+        }
+    }
+
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Help => println!("{}", USAGE),
+            Command::Factor {
+                path,
+                dialect,
+                side_by_side,
+            } => {
+                let grammar = read_grammar(&path, dialect)?;
+                let rewrite = grammar.left_factor();
+                if side_by_side {
+                    rewrite.print_side_by_side(&grammar);
+                } else {
+                    rewrite.grammar.print_rules();
+                }
+            }
+            Command::Ll1ify {
+                path,
+                dialect,
+                side_by_side,
+            } => {
+                let grammar = read_grammar(&path, dialect)?;
+                let result = grammar.ll1ify();
+                println!("{}\n", result);
+                if side_by_side {
+                    result.rewrite.print_side_by_side(&grammar);
+                } else {
+                    result.rewrite.grammar.print_rules();
+                }
+            }
+            Command::Conflicts { path, dialect } => {
+                let grammar = read_grammar(&path, dialect)?;
+                let report = grammar.conflict_report();
+                if report.is_empty() {
+                    println!("The grammar is LL(1)");
+                    return Ok(());
+                }
+                let counterexamples = grammar.counterexamples();
+                println!("{} conflict(s):", report.len());
+                for conflict in &report.conflicts {
+                    println!();
+                    match counterexamples.iter().find(|c| c.conflict == *conflict) {
+                        Some(counterexample) => println!("{}", counterexample),
+                        None => println!("{}\n   No counterexample found", conflict),
+                    }
+                }
+            }
+            Command::Fmt {
+                paths,
+                dialect,
+                check,
+                width,
+            } => {
+                let mut unformatted = 0;
+                for path in &paths {
+                    let source = Grammar::read_file(path)?;
+                    let dialect = dialect.unwrap_or_else(|| Dialect::detect(&source));
+                    let grammar = Grammar::read(&source, None, dialect)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    validate(&grammar, path)?;
+                    let formatted = Grammar::format_source(&source, dialect, width)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    if formatted == source {
+                        continue;
+                    }
+                    if check {
+                        println!("{}", path.display());
+                        unformatted += 1;
+                    } else {
+                        fs::write(path, formatted)
+                            .map_err(|e| format!("Cannot write '{}': {}", path.display(), e))?;
+                    }
+                }
+                if unformatted > 0 {
+                    return Err(format!(
+                        "{} file(s) are not formatted; run `ll1 fmt` on them",
+                        unformatted
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_grammar(path: &Path, dialect: Option<Dialect>) -> Result<Grammar, Box<dyn Error>> {
+    let grammar = match dialect {
+        Some(dialect) => Grammar::from_file_in(path, dialect)?,
+        None => Grammar::from_file(path)?,
+    };
+    validate(&grammar, path)?;
+    Ok(grammar)
+}
+
+/// Prints the diagnostics of the grammar read from `path` and fails if any
+/// of them is an error
+fn validate(grammar: &Grammar, path: &Path) -> Result<(), Box<dyn Error>> {
+    let diagnostics = grammar.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", path.display(), diagnostic);
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        return Err(format!("{}: the grammar has errors", path.display()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Command;
+
+    fn fmt(args: &[&str]) -> Result<(), String> {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.insert(0, "fmt".to_string());
+        let command = Command::parse(&args)?.expect("fmt is a command");
+        command.run().map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn fmt_check_fails_on_unformatted_files_without_rewriting_them() {
+        let path = std::env::temp_dir().join(format!("ll1-fmt-check-{}.txt", std::process::id()));
+        let path_arg = path.to_str().unwrap();
+        let source = "E->T X\nX->'+' T X|ε\nT->id\n";
+        fs::write(&path, source).unwrap();
+
+        assert!(fmt(&["--check", path_arg]).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+        fmt(&[path_arg]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "E -> T X\nX -> '+' T X | ε\nT -> id\n"
+        );
+        assert!(fmt(&["--check", path_arg]).is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
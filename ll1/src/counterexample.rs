@@ -0,0 +1,478 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+
+use super::grammar::Production;
+use super::lexer::quote_terminal;
+use super::table::{Conflict, ParsingTable};
+use super::Grammar;
+
+/// Most sentential forms visited while looking for the place of a conflict
+const MAX_FORMS: usize = 10_000;
+
+/// Length of the terminal string a derivation yields, then its number of steps
+type Cost = (usize, usize);
+
+/// A leftmost derivation from the start symbol to a sentence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Derivation {
+    /// The production applied at the conflict
+    pub production: Production,
+    /// Every sentential form, from the start symbol to the sentence
+    pub forms: Vec<Vec<String>>,
+}
+
+/// Two or more derivations that agree up to a conflict and then take the
+/// competing productions, with the next terminal the same in all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub conflict: Conflict,
+    /// The terminals read before the parser has to choose
+    pub prefix: Vec<String>,
+    /// Index in every derivation's `forms` of the sentential form `w A β`
+    /// where the parser has to choose, `w` being `prefix`
+    pub split: usize,
+    /// One derivation per competing production, in the conflict's order
+    pub derivations: Vec<Derivation>,
+    /// The terminals among the derivations' symbols, quoted when displayed
+    pub terminals: IndexSet<String>,
+}
+
+/// Shortest derivations of the non-terminals of a grammar
+struct Shortest<'a> {
+    grammar: &'a Grammar,
+    /// The production starting the shortest derivation of each non-terminal
+    yields: IndexMap<&'a str, (Cost, &'a Production)>,
+    /// For each non-terminal `A` and terminal `a`, the cost of the shortest
+    /// sentence `a …` that `A` derives, the production starting that
+    /// derivation, and the position in it of the symbol that derives `a`
+    starting_with: IndexMap<(&'a str, &'a str), (Cost, &'a Production, usize)>,
+}
+
+impl Grammar {
+    /// A counterexample for every conflict of the parsing table, in the
+    /// order of `ParsingTable::conflict_report`, leaving out those it cannot build
+    pub fn counterexamples(&self) -> Vec<Counterexample> {
+        let shortest = Shortest::new(self);
+        ParsingTable::conflict_report(self)
+            .conflicts
+            .into_iter()
+            .filter_map(|conflict| shortest.counterexample(conflict))
+            .collect()
+    }
+
+    /// A counterexample for `conflict`: the shortest leftmost derivation
+    /// found of a sentential form `w A β` where `A` is the conflict's
+    /// non-terminal and every competing production can go on to derive a
+    /// sentence `w a …`, `a` being the conflict's terminal, followed by one
+    /// such derivation per production.
+    ///
+    /// Returns `None` when no such form is found among the first sentential
+    /// forms searched, as for a non-terminal that cannot be reached.
+    pub fn counterexample(&self, conflict: &Conflict) -> Option<Counterexample> {
+        Shortest::new(self).counterexample(conflict.clone())
+    }
+}
+
+impl<'a> Shortest<'a> {
+    fn new(grammar: &'a Grammar) -> Self {
+        let mut shortest = Shortest {
+            grammar,
+            yields: IndexMap::new(),
+            starting_with: IndexMap::new(),
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &grammar.productions {
+                let symbols = Self::symbols(&production.derivation);
+                let Some(cost) = shortest.cost_of(&symbols) else {
+                    continue;
+                };
+                let cost = (cost.0, cost.1 + 1);
+                let entry = shortest.yields.get(production.non_terminal.as_str());
+                if entry.is_none_or(|(best, _)| cost < *best) {
+                    shortest
+                        .yields
+                        .insert(&production.non_terminal, (cost, production));
+                    changed = true;
+                }
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &grammar.productions {
+                let symbols = Self::symbols(&production.derivation);
+                for terminal in &grammar.terminals {
+                    let Some((cost, position)) = shortest.cost_starting_with(&symbols, terminal)
+                    else {
+                        continue;
+                    };
+                    let cost = (cost.0, cost.1 + 1);
+                    let key = (production.non_terminal.as_str(), terminal.as_str());
+                    let entry = shortest.starting_with.get(&key);
+                    if entry.is_none_or(|(best, _, _)| cost < *best) {
+                        shortest
+                            .starting_with
+                            .insert(key, (cost, production, position));
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        shortest
+    }
+
+    fn counterexample(&self, conflict: Conflict) -> Option<Counterexample> {
+        let start = vec![self.grammar.start_symbol.clone()];
+        let mut queue: VecDeque<(Vec<String>, Vec<&Production>)> =
+            VecDeque::from([(start.clone(), Vec::new())]);
+        let mut seen: HashSet<Vec<String>> = HashSet::from([start.clone()]);
+
+        // Leftmost derivations where the terminals before the leftmost
+        // non-terminal are set aside, searched by number of steps
+        while let Some((rest, steps)) = queue.pop_front() {
+            if rest[0] == conflict.non_terminal {
+                if let Some(choices) = self.choices(&conflict, &rest[1..]) {
+                    let split = self.apply(&start, &steps).len() - 1;
+                    let derivations: Vec<Derivation> = conflict
+                        .productions
+                        .iter()
+                        .zip(choices)
+                        .map(|(production, choice)| {
+                            let all: Vec<&Production> =
+                                steps.iter().copied().chain(choice).collect();
+                            Derivation {
+                                production: production.clone(),
+                                forms: self.apply(&start, &all),
+                            }
+                        })
+                        .collect();
+                    let prefix = self.apply(&start, &steps)[split]
+                        .iter()
+                        .take_while(|symbol| !self.grammar.non_terminals.contains(*symbol))
+                        .cloned()
+                        .collect();
+                    let terminals = derivations
+                        .iter()
+                        .flat_map(|derivation| derivation.forms.iter().flatten())
+                        .filter(|symbol| self.grammar.terminals.contains(*symbol))
+                        .cloned()
+                        .collect();
+                    return Some(Counterexample {
+                        conflict,
+                        prefix,
+                        split,
+                        derivations,
+                        terminals,
+                    });
+                }
+            }
+            if seen.len() > MAX_FORMS {
+                return None;
+            }
+
+            let mut next: Vec<(Vec<String>, Vec<&Production>)> = Vec::new();
+            for production in self.productions_of(&rest[0]) {
+                let mut form = Self::symbols(&production.derivation);
+                form.extend(rest[1..].iter().cloned());
+                let mut steps = steps.clone();
+                steps.push(production);
+                next.push((form, steps));
+            }
+            // Or derive the leftmost non-terminal to its shortest sentence
+            if rest.len() > 1 {
+                let mut steps = steps.clone();
+                self.shortest_steps(&rest[0], &mut steps);
+                next.push((rest[1..].to_vec(), steps));
+            }
+
+            for (form, steps) in next {
+                let rest: Vec<String> = form
+                    .into_iter()
+                    .skip_while(|symbol| self.grammar.terminals.contains(symbol))
+                    .collect();
+                if !rest.is_empty() && seen.insert(rest.clone()) {
+                    queue.push_back((rest, steps));
+                }
+            }
+        }
+        None
+    }
+
+    /// For each competing production `A → α`, the steps deriving `α β` to
+    /// the shortest sentence that starts with the conflict's terminal,
+    /// the production itself first
+    fn choices(&self, conflict: &Conflict, context: &[String]) -> Option<Vec<Vec<&'a Production>>> {
+        conflict
+            .productions
+            .iter()
+            .map(|production| {
+                let production = self.grammar.productions.iter().find(|p| *p == production)?;
+                let mut symbols = Self::symbols(&production.derivation);
+                symbols.extend(context.iter().cloned());
+                let mut steps = vec![production];
+                self.steps_starting_with(&symbols, &conflict.terminal, &mut steps)?;
+                Some(steps)
+            })
+            .collect()
+    }
+
+    /// Appends the steps deriving `symbols` to their shortest sentence
+    /// starting with `terminal`, or to ε when `terminal` is `$`
+    fn steps_starting_with(
+        &self,
+        symbols: &[String],
+        terminal: &str,
+        steps: &mut Vec<&'a Production>,
+    ) -> Option<()> {
+        let position = if terminal == "$" {
+            self.cost_of(symbols).filter(|cost| cost.0 == 0)?;
+            symbols.len()
+        } else {
+            self.cost_starting_with(symbols, terminal)?.1
+        };
+        for (i, symbol) in symbols.iter().enumerate() {
+            if i == position {
+                self.steps_of_start(symbol, terminal, steps);
+            } else {
+                self.shortest_steps(symbol, steps);
+            }
+        }
+        Some(())
+    }
+
+    /// Appends the steps of the shortest derivation of `symbol`
+    fn shortest_steps(&self, symbol: &str, steps: &mut Vec<&'a Production>) {
+        let Some((_, production)) = self.yields.get(symbol) else {
+            return;
+        };
+        steps.push(production);
+        for child in Self::symbols(&production.derivation) {
+            self.shortest_steps(&child, steps);
+        }
+    }
+
+    /// Appends the steps of the shortest derivation of `symbol` to a sentence
+    /// starting with `terminal`
+    fn steps_of_start(&self, symbol: &str, terminal: &str, steps: &mut Vec<&'a Production>) {
+        let Some((_, production, position)) = self.starting_with.get(&(symbol, terminal)) else {
+            return;
+        };
+        steps.push(production);
+        for (i, child) in Self::symbols(&production.derivation).iter().enumerate() {
+            if i == *position {
+                self.steps_of_start(child, terminal, steps);
+            } else {
+                self.shortest_steps(child, steps);
+            }
+        }
+    }
+
+    /// Cost of deriving every symbol to its shortest sentence
+    fn cost_of(&self, symbols: &[String]) -> Option<Cost> {
+        symbols.iter().try_fold((0, 0), |total, symbol| {
+            let cost = self.symbol_cost(symbol)?;
+            Some((total.0 + cost.0, total.1 + cost.1))
+        })
+    }
+
+    /// Cost of the shortest sentence of `symbols` starting with `terminal`,
+    /// and the position of the symbol that derives `terminal`
+    fn cost_starting_with(&self, symbols: &[String], terminal: &str) -> Option<(Cost, usize)> {
+        let mut best: Option<(Cost, usize)> = None;
+        for (position, symbol) in symbols.iter().enumerate() {
+            let head = if symbol == terminal {
+                Some((1, 0))
+            } else {
+                self.starting_with
+                    .get(&(symbol.as_str(), terminal))
+                    .map(|(cost, _, _)| *cost)
+            };
+            let before = self.cost_of(&symbols[..position]);
+            let after = self.cost_of(&symbols[position + 1..]);
+            if let (Some(head), Some(before), Some(after)) = (head, before, after) {
+                let cost = (before.0 + head.0 + after.0, before.1 + head.1 + after.1);
+                if before.0 == 0 && best.is_none_or(|(best, _)| cost < best) {
+                    best = Some((cost, position));
+                }
+            }
+            // Later symbols only come first when this one can derive ε
+            if self.symbol_cost(symbol).is_none_or(|cost| cost.0 > 0) {
+                break;
+            }
+        }
+        best
+    }
+
+    fn symbol_cost(&self, symbol: &str) -> Option<Cost> {
+        if self.grammar.terminals.contains(symbol) {
+            return Some((1, 0));
+        }
+        self.yields.get(symbol).map(|(cost, _)| *cost)
+    }
+
+    fn productions_of(&self, non_terminal: &str) -> impl Iterator<Item = &'a Production> {
+        let non_terminal = non_terminal.to_string();
+        self.grammar
+            .productions
+            .iter()
+            .filter(move |production| production.non_terminal == non_terminal)
+    }
+
+    /// The sentential forms of the leftmost derivation from `start` that
+    /// applies `steps` in order
+    fn apply(&self, start: &[String], steps: &[&Production]) -> Vec<Vec<String>> {
+        let mut forms = vec![start.to_vec()];
+        for production in steps {
+            let mut form = forms.last().unwrap().clone();
+            let Some(leftmost) = form
+                .iter()
+                .position(|symbol| self.grammar.non_terminals.contains(symbol))
+            else {
+                break;
+            };
+            form.splice(leftmost..=leftmost, Self::symbols(&production.derivation));
+            forms.push(form);
+        }
+        forms
+    }
+
+    /// A derivation without its ε
+    fn symbols(derivation: &[String]) -> Vec<String> {
+        derivation.iter().filter(|s| *s != "ε").cloned().collect()
+    }
+}
+
+impl Derivation {
+    /// The sentence the derivation ends with
+    pub fn sentence(&self) -> &[String] {
+        self.forms.last().map_or(&[], Vec::as_slice)
+    }
+}
+
+impl Counterexample {
+    /// `symbol`, quoted when it is a terminal that needs it
+    fn quote(&self, symbol: &String) -> String {
+        if self.terminals.contains(symbol) {
+            quote_terminal(symbol)
+        } else {
+            symbol.clone()
+        }
+    }
+
+    /// Writes a sentential form, `ε` when it is empty
+    fn write_form(&self, f: &mut fmt::Formatter<'_>, form: &[String]) -> fmt::Result {
+        if form.is_empty() {
+            write!(f, "ε")
+        } else {
+            let symbols: Vec<String> = form.iter().map(|symbol| self.quote(symbol)).collect();
+            write!(f, "{}", symbols.join(" "))
+        }
+    }
+}
+
+/// Displays the conflict, where the parser has to choose, the derivation
+/// up to there, then the rest of each derivation
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.conflict)?;
+        let lookahead = if self.conflict.terminal == "$" {
+            "the end of the input".to_string()
+        } else {
+            format!("'{}'", self.conflict.terminal)
+        };
+        if self.prefix.is_empty() {
+            write!(f, "   At the start of the input")?;
+        } else {
+            write!(f, "   After '{}'", self.prefix.join(" "))?;
+        }
+        writeln!(
+            f,
+            ", with {} to expand and {} next:",
+            self.conflict.non_terminal, lookahead
+        )?;
+
+        let Some(first) = self.derivations.first() else {
+            return Ok(());
+        };
+        write!(f, "   ")?;
+        for (i, form) in first.forms[..=self.split].iter().enumerate() {
+            if i > 0 {
+                write!(f, " ⇒ ")?;
+            }
+            self.write_form(f, form)?;
+        }
+        for derivation in &self.derivations {
+            let production = &derivation.production;
+            let symbols: Vec<String> = production
+                .derivation
+                .iter()
+                .map(|symbol| self.quote(symbol))
+                .collect();
+            write!(
+                f,
+                "\n   {} → {}:",
+                production.non_terminal,
+                symbols.join(" ")
+            )?;
+            for form in &derivation.forms[self.split + 1..] {
+                write!(f, " ⇒ ")?;
+                self.write_form(f, form)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    fn forms(derivation: &super::Derivation) -> Vec<String> {
+        derivation.forms.iter().map(|form| form.join(" ")).collect()
+    }
+
+    #[test]
+    fn explains_the_dangling_else() {
+        let source = "S -> i E t S S' | a\nS' -> e S | ε\nE -> b\n";
+        let grammar = Grammar::from_string(source, "S").unwrap();
+        let counterexamples = grammar.counterexamples();
+        assert_eq!(counterexamples.len(), 1);
+
+        let counterexample = &counterexamples[0];
+        assert_eq!(counterexample.prefix, ["i", "b", "t", "i", "b", "t", "a"]);
+        let sentences: Vec<&[String]> = counterexample
+            .derivations
+            .iter()
+            .map(|derivation| derivation.sentence())
+            .collect();
+        assert_eq!(sentences[0], sentences[1]);
+        assert_eq!(sentences[0].join(" "), "i b t i b t a e a");
+    }
+
+    #[test]
+    fn explains_a_left_recursive_rule() {
+        let grammar = Grammar::from_string("E -> E '+' T | T\nT -> id\n", "E").unwrap();
+        let counterexamples = grammar.counterexamples();
+        assert_eq!(counterexamples.len(), 1);
+
+        let counterexample = &counterexamples[0];
+        assert!(counterexample.prefix.is_empty());
+        assert_eq!(counterexample.split, 0);
+        assert_eq!(
+            forms(&counterexample.derivations[0]),
+            ["E", "E + T", "T + T", "id + T", "id + id"]
+        );
+        assert_eq!(forms(&counterexample.derivations[1]), ["E", "T", "id"]);
+
+        let text = counterexample.to_string();
+        assert!(text.contains("E → E '+' T: ⇒ E '+' T ⇒ T '+' T ⇒ id '+' T ⇒ id '+' id"));
+        assert!(text.contains("E → T: ⇒ T ⇒ id"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::lexer::Lexer;
+
+/// Notations a grammar can be written in.
+///
+/// All dialects share comments, quoted literals, `%` directives, `|`
+/// alternatives, `;` terminators and EBNF operators; they differ in how a
+/// rule is introduced and in how the kind of a bare symbol is decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    /// `E -> T E'`, non-terminals are the rule names unless `%symbols case`
+    /// decides by letter case
+    #[default]
+    Arrow,
+    /// `E → T E'`, non-terminals as in `Arrow`
+    Unicode,
+    /// `<expr> ::= <term> "+" <expr>`, non-terminals in angle brackets
+    Bnf,
+    /// `expr : term '+' expr ;`, non-terminals are the rule names
+    Yacc,
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [
+        Dialect::Arrow,
+        Dialect::Unicode,
+        Dialect::Bnf,
+        Dialect::Yacc,
+    ];
+
+    /// Guesses the dialect of grammar source text from the first line that
+    /// defines a rule once comments are stripped, defaulting to `Arrow`
+    pub fn detect(input: &str) -> Dialect {
+        for line in Lexer::strip_comments(input).lines() {
+            let line = line.trim_start();
+            if line.starts_with('%') {
+                continue;
+            }
+            let markers = [
+                (line.find("::="), Dialect::Bnf),
+                (line.find('→'), Dialect::Unicode),
+                (line.find("->"), Dialect::Arrow),
+            ];
+            let first = markers
+                .into_iter()
+                .filter_map(|(position, dialect)| Some((position?, dialect)))
+                .min_by_key(|(position, _)| *position);
+            if let Some((_, dialect)) = first {
+                return dialect;
+            }
+            if Self::is_yacc_rule(line) {
+                return Dialect::Yacc;
+            }
+        }
+        Dialect::Arrow
+    }
+
+    /// Whether bare symbols get their kind from the notation rather than
+    /// from the rules or, under `%symbols case`, from letter case
+    pub fn kinds_by_syntax(&self) -> bool {
+        matches!(self, Dialect::Bnf | Dialect::Yacc)
+    }
+
+    /// The token separating a rule's name from its alternatives
+    pub fn definer(&self) -> &'static str {
+        match self {
+            Dialect::Arrow => "->",
+            Dialect::Unicode => "→",
+            Dialect::Bnf => "::=",
+            Dialect::Yacc => ":",
+        }
+    }
+
+    /// `name :` or a continuation line starting with `:`
+    fn is_yacc_rule(line: &str) -> bool {
+        let rest = line.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.');
+        let rest = rest.trim_start();
+        rest.starts_with(':') && !rest.starts_with("::")
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dialect::Arrow => write!(f, "arrow"),
+            Dialect::Unicode => write!(f, "unicode"),
+            Dialect::Bnf => write!(f, "bnf"),
+            Dialect::Yacc => write!(f, "yacc"),
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Dialect::ALL
+            .into_iter()
+            .find(|dialect| dialect.to_string() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown dialect '{}' (expected arrow, unicode, bnf or yacc)",
+                    name
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dialect;
+
+    #[test]
+    fn ignores_arrows_in_comments() {
+        let input = "/* written as\n   E -> T */\n<expr> ::= <term>\n";
+        assert_eq!(Dialect::detect(input), Dialect::Bnf);
+        let input = "expr : term ; // not E -> T\n";
+        assert_eq!(Dialect::detect(input), Dialect::Yacc);
+    }
+
+    #[test]
+    fn reads_markers_in_literals_and_primes() {
+        assert_eq!(Dialect::detect("E' → '->' T\n"), Dialect::Unicode);
+        assert_eq!(Dialect::detect("A -> '#' B\n"), Dialect::Arrow);
+    }
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Location of a piece of grammar source text: 1-based line and column,
+/// and its length in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+/// Errors raised while reading or constructing a grammar
+#[derive(Debug)]
+pub enum GrammarError {
+    /// The grammar file could not be read
+    Io { path: PathBuf, source: io::Error },
+    /// The source holds no rules and names no start symbol
+    EmptyGrammar,
+    /// A character that cannot start any token
+    UnexpectedCharacter { character: char, span: Span },
+    /// A quoted literal without its closing quote
+    UnterminatedLiteral { span: Span },
+    /// `''` or `""`
+    EmptyLiteral { span: Span },
+    /// A literal spelling a reserved symbol (`ε` or `$`)
+    ReservedLiteral { literal: String, span: Span },
+    /// An unknown or malformed escape sequence inside a literal
+    InvalidEscape { sequence: String, span: Span },
+    /// A `%` directive that does not exist
+    UnknownDirective { name: String, span: Span },
+    /// A known directive with missing or wrong arguments
+    InvalidDirective {
+        name: String,
+        reason: String,
+        span: Span,
+    },
+    /// A token that does not fit where it appears
+    UnexpectedToken { found: String, span: Span },
+    /// A line that is neither a rule, a continuation nor a directive
+    ExpectedArrow { span: Span },
+    /// A `(` without its `)`
+    UnclosedGroup { span: Span },
+    /// Two EBNF operators in a row, such as `a*?`
+    StackedOperator { span: Span },
+    /// The start symbol is not a usable non-terminal, or is given twice
+    InvalidStartSymbol {
+        symbol: String,
+        reason: String,
+        span: Option<Span>,
+    },
+    /// The left-hand side of a rule is not a usable non-terminal
+    InvalidNonTerminal {
+        symbol: String,
+        reason: String,
+        span: Span,
+    },
+    /// A symbol in a derivation that is neither a terminal nor a non-terminal
+    InvalidSymbol { symbol: String, span: Span },
+    /// A terminal spelled like a non-terminal of the grammar
+    SymbolClash { symbol: String, span: Span },
+    /// A non-terminal used in a rule built in code but never given a rule
+    UndefinedNonTerminal { symbol: String, used_by: String },
+    /// A symbol used in code both as a terminal and as a non-terminal
+    KindConflict { symbol: String },
+    /// A terminal given in code that is empty or reserved
+    InvalidTerminal { symbol: String, reason: String },
+    /// A non-terminal derives itself, `A ⇒+ A`, through the non-terminals in `cycle`
+    CyclicGrammar { cycle: Vec<String> },
+    /// A non-terminal that `Grammar::inline` cannot replace by its alternatives
+    CannotInline { symbol: String, reason: String },
+    /// A sequence that `Grammar::extract_rule` cannot pull out into a new rule
+    CannotExtract {
+        sequence: Vec<String>,
+        reason: String,
+    },
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// The grammar cannot be analysed as written
+    Error,
+    /// The grammar can be analysed but is probably not what was meant
+    Warning,
+}
+
+/// A problem found in a grammar that was read successfully
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Self {
+            line,
+            column,
+            length,
+        }
+    }
+}
+
+impl GrammarError {
+    /// Source location of the offending text, when there is one
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            GrammarError::Io { .. }
+            | GrammarError::EmptyGrammar
+            | GrammarError::UndefinedNonTerminal { .. }
+            | GrammarError::KindConflict { .. }
+            | GrammarError::InvalidTerminal { .. }
+            | GrammarError::CyclicGrammar { .. }
+            | GrammarError::CannotInline { .. }
+            | GrammarError::CannotExtract { .. } => None,
+            GrammarError::InvalidStartSymbol { span, .. } => *span,
+            GrammarError::UnexpectedCharacter { span, .. }
+            | GrammarError::UnterminatedLiteral { span }
+            | GrammarError::EmptyLiteral { span }
+            | GrammarError::ReservedLiteral { span, .. }
+            | GrammarError::InvalidEscape { span, .. }
+            | GrammarError::UnknownDirective { span, .. }
+            | GrammarError::InvalidDirective { span, .. }
+            | GrammarError::UnexpectedToken { span, .. }
+            | GrammarError::ExpectedArrow { span }
+            | GrammarError::UnclosedGroup { span }
+            | GrammarError::StackedOperator { span }
+            | GrammarError::InvalidNonTerminal { span, .. }
+            | GrammarError::InvalidSymbol { span, .. }
+            | GrammarError::SymbolClash { span, .. } => Some(*span),
+        }
+    }
+
+    /// The location of the error, for importers mapping it back to their input
+    pub(crate) fn span_mut(&mut self) -> Option<&mut Span> {
+        match self {
+            GrammarError::Io { .. }
+            | GrammarError::EmptyGrammar
+            | GrammarError::UndefinedNonTerminal { .. }
+            | GrammarError::KindConflict { .. }
+            | GrammarError::InvalidTerminal { .. }
+            | GrammarError::CyclicGrammar { .. }
+            | GrammarError::CannotInline { .. }
+            | GrammarError::CannotExtract { .. } => None,
+            GrammarError::InvalidStartSymbol { span, .. } => span.as_mut(),
+            GrammarError::UnexpectedCharacter { span, .. }
+            | GrammarError::UnterminatedLiteral { span }
+            | GrammarError::EmptyLiteral { span }
+            | GrammarError::ReservedLiteral { span, .. }
+            | GrammarError::InvalidEscape { span, .. }
+            | GrammarError::UnknownDirective { span, .. }
+            | GrammarError::InvalidDirective { span, .. }
+            | GrammarError::UnexpectedToken { span, .. }
+            | GrammarError::ExpectedArrow { span }
+            | GrammarError::UnclosedGroup { span }
+            | GrammarError::StackedOperator { span }
+            | GrammarError::InvalidNonTerminal { span, .. }
+            | GrammarError::InvalidSymbol { span, .. }
+            | GrammarError::SymbolClash { span, .. } => Some(span),
+        }
+    }
+
+    /// The message without its location
+    fn message(&self) -> String {
+        match self {
+            GrammarError::Io { path, source } => {
+                format!("Cannot read '{}': {}", path.display(), source)
+            }
+            GrammarError::EmptyGrammar => "Empty grammar: no start symbol".to_string(),
+            GrammarError::UnexpectedCharacter { character, .. } => format!(
+                "Unexpected character '{}'; quote it to use it as a terminal ('{}')",
+                character, character
+            ),
+            GrammarError::UnterminatedLiteral { .. } => "Unterminated literal".to_string(),
+            GrammarError::EmptyLiteral { .. } => {
+                "Empty literal; use ε for the empty string".to_string()
+            }
+            GrammarError::ReservedLiteral { literal, .. } => {
+                format!("Literal '{}' is reserved", literal)
+            }
+            GrammarError::InvalidEscape { sequence, .. } => {
+                format!("Invalid escape sequence '{}'", sequence)
+            }
+            GrammarError::UnknownDirective { name, .. } => format!(
+                "Unknown directive '%{}' (expected %start, %token, %epsilon, %name, %symbols or %order)",
+                name
+            ),
+            GrammarError::InvalidDirective { name, reason, .. } => {
+                format!("%{} {}", name, reason)
+            }
+            GrammarError::UnexpectedToken { found, .. } => format!("Unexpected {}", found),
+            GrammarError::ExpectedArrow { .. } => "Expected '->' in production".to_string(),
+            GrammarError::UnclosedGroup { .. } => "Unclosed '('".to_string(),
+            GrammarError::StackedOperator { .. } => {
+                "EBNF operators cannot be stacked; use a group".to_string()
+            }
+            GrammarError::InvalidStartSymbol { symbol, reason, .. } => {
+                format!("Start symbol '{}' {}", symbol, reason)
+            }
+            GrammarError::InvalidNonTerminal { symbol, reason, .. } => {
+                format!("Non-terminal '{}' {}", symbol, reason)
+            }
+            GrammarError::InvalidSymbol { symbol, .. } => {
+                format!("Invalid symbol '{}' in derivation", symbol)
+            }
+            GrammarError::SymbolClash { symbol, .. } => format!(
+                "Terminal '{}' clashes with the non-terminal of the same name",
+                symbol
+            ),
+            GrammarError::UndefinedNonTerminal { symbol, used_by } => format!(
+                "Non-terminal '{}' is used by '{}' but has no rule",
+                symbol, used_by
+            ),
+            GrammarError::KindConflict { symbol } => format!(
+                "Symbol '{}' is used both as a terminal and as a non-terminal",
+                symbol
+            ),
+            GrammarError::InvalidTerminal { symbol, reason } => {
+                format!("Terminal '{}' {}", symbol, reason)
+            }
+            GrammarError::CyclicGrammar { cycle } => format!(
+                "Grammar is cyclic: {} ⇒+ {} through {}",
+                cycle[0],
+                cycle[0],
+                cycle
+                    .iter()
+                    .chain(&cycle[..1])
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" → ")
+            ),
+            GrammarError::CannotInline { symbol, reason } => {
+                format!("Cannot inline '{}': {}", symbol, reason)
+            }
+            GrammarError::CannotExtract { sequence, reason } => {
+                format!("Cannot extract '{}': {}", sequence.join(" "), reason)
+            }
+        }
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write!(
+                f,
+                "Error on line {}, column {}: {}",
+                span.line,
+                span.column,
+                self.message()
+            ),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl Error for GrammarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrammarError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            span,
+        }
+    }
+
+    pub fn warning(message: String, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            span,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(
+                f,
+                "{} on line {}, column {}: {}",
+                self.severity, span.line, span.column, self.message
+            ),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
@@ -1,24 +1,28 @@
-use std::collections::{HashMap, HashSet};
+use indexmap::{IndexMap, IndexSet};
 
+use super::grammar::SymbolOrder;
 use super::Grammar;
 
+/// A set of symbols for each symbol, such as the FIRST or FOLLOW sets
+pub type SymbolSets = IndexMap<String, IndexSet<String>>;
+
 impl Grammar {
     /*
     Calculates the FIRST set for each terminal and non-terminal.
     It iterates through productions, propagates FIRST symbols,
     and handles nullability ("ε") until no changes occur.
     */
-    pub fn compute_first_sets(&self) -> HashMap<String, HashSet<String>> {
-        let mut first_sets: HashMap<String, HashSet<String>> = HashMap::new();
+    pub fn compute_first_sets(&self) -> SymbolSets {
+        let mut first_sets: SymbolSets = IndexMap::new();
 
         for terminal in &self.terminals {
-            let mut set = HashSet::new();
+            let mut set = IndexSet::new();
             set.insert(terminal.clone());
             first_sets.insert(terminal.clone(), set);
         }
 
         for non_terminal in &self.non_terminals {
-            first_sets.insert(non_terminal.clone(), HashSet::new());
+            first_sets.insert(non_terminal.clone(), IndexSet::new());
         }
 
         let mut changed = true;
@@ -28,17 +32,11 @@ impl Grammar {
             for production in &self.productions {
                 let nt = &production.non_terminal;
 
-                if production.derivation[0] == "ε" {
-                    if first_sets.get_mut(nt).unwrap().insert("ε".to_string()) {
-                        changed = true;
-                    }
-                    continue;
-                }
-
                 let mut all_nullable = true;
-                let mut first_set = HashSet::new();
+                let mut first_set = IndexSet::new();
 
-                for symbol in &production.derivation {
+                // ε derives nothing, wherever it appears in the derivation
+                for symbol in production.derivation.iter().filter(|s| *s != "ε") {
                     if let Some(symbol_first) = first_sets.get(symbol) {
                         for terminal in symbol_first {
                             if terminal != "ε" {
@@ -67,6 +65,7 @@ impl Grammar {
             }
         }
 
+        self.order_sets(&mut first_sets);
         first_sets
     }
 
@@ -75,15 +74,12 @@ impl Grammar {
     It tracks terminal symbols and nullability following each symbol
     in productions and propagates updates iteratively.
     */
-    pub fn compute_follow_sets(
-        &self,
-        first_sets: &HashMap<String, HashSet<String>>,
-    ) -> HashMap<String, HashSet<String>> {
-        let mut follow_sets: HashMap<String, HashSet<String>> = HashMap::new();
+    pub fn compute_follow_sets(&self, first_sets: &SymbolSets) -> SymbolSets {
+        let mut follow_sets: SymbolSets = IndexMap::new();
 
         // Initialize FOLLOW sets
         for non_terminal in &self.non_terminals {
-            follow_sets.insert(non_terminal.clone(), HashSet::new());
+            follow_sets.insert(non_terminal.clone(), IndexSet::new());
         }
 
         // Add $ to follow set of start symbol
@@ -104,7 +100,7 @@ impl Grammar {
                     let current = &production.derivation[i];
 
                     if self.non_terminals.contains(current) {
-                        let mut first_of_rest = HashSet::new();
+                        let mut first_of_rest = IndexSet::new();
                         let mut all_nullable = true;
 
                         // Compute FIRST of everything that follows
@@ -150,6 +146,7 @@ impl Grammar {
             }
         }
 
+        self.order_sets(&mut follow_sets);
         follow_sets
     }
 
@@ -161,9 +158,9 @@ impl Grammar {
     pub fn compute_first_of_string(
         &self,
         string: &[String],
-        first_sets: &HashMap<String, HashSet<String>>,
-    ) -> HashSet<String> {
-        let mut result = HashSet::new();
+        first_sets: &SymbolSets,
+    ) -> IndexSet<String> {
+        let mut result = IndexSet::new();
         let mut all_nullable = true;
 
         for symbol in string {
@@ -187,4 +184,80 @@ impl Grammar {
 
         result
     }
+
+    /// Non-terminals that derive ε, read off the FIRST sets
+    pub fn nullable_non_terminals(&self) -> IndexSet<String> {
+        self.compute_first_sets()
+            .into_iter()
+            .filter(|(symbol, first)| self.non_terminals.contains(symbol) && first.contains("ε"))
+            .map(|(symbol, _)| symbol)
+            .collect()
+    }
+
+    /// Sorts the sets and their contents when the grammar asks for sorted output
+    fn order_sets(&self, sets: &mut SymbolSets) {
+        if self.order == SymbolOrder::Sorted {
+            sets.sort_keys();
+            for set in sets.values_mut() {
+                set.sort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::GrammarBuilder;
+    use crate::grammar::SymbolOrder;
+    use crate::{Grammar, ParsingTable};
+
+    const SOURCE: &str = "S -> b B | a A\nB -> z | ε\nA -> y\n";
+
+    /// Everything the analysis lists, rendered in output order
+    fn output(grammar: &Grammar) -> String {
+        let first_sets = grammar.compute_first_sets();
+        let follow_sets = grammar.compute_follow_sets(&first_sets);
+        let table = ParsingTable::build(grammar).unwrap();
+        format!("{:?}\n{:?}\n{}", first_sets, follow_sets, table)
+    }
+
+    #[test]
+    fn source_order_is_the_same_on_every_run() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        assert_eq!(grammar.order, SymbolOrder::Source);
+        let keys: Vec<String> = grammar
+            .compute_first_sets()
+            .into_keys()
+            .filter(|symbol| grammar.non_terminals.contains(symbol))
+            .collect();
+        assert_eq!(keys, ["S", "B", "A"]);
+        for _ in 0..5 {
+            let again = Grammar::from_string(SOURCE, "S").unwrap();
+            assert_eq!(output(&again), output(&grammar));
+        }
+    }
+
+    #[test]
+    fn sorted_order_is_the_same_on_every_run() {
+        let source = format!("%order sorted\n{}", SOURCE);
+        let grammar = Grammar::from_string(&source, "S").unwrap();
+        assert_eq!(grammar.order, SymbolOrder::Sorted);
+        let keys: Vec<String> = grammar.compute_first_sets().into_keys().collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+
+        let built = GrammarBuilder::new()
+            .order(SymbolOrder::Sorted)
+            .rule("S", |r| r.t("b").nt("B").or().t("a").nt("A"))
+            .rule("B", |r| r.t("z").or().eps())
+            .rule("A", |r| r.t("y"))
+            .build()
+            .unwrap();
+        for _ in 0..5 {
+            let again = Grammar::from_string(&source, "S").unwrap();
+            assert_eq!(output(&again), output(&grammar));
+            assert_eq!(output(&built), output(&grammar));
+        }
+    }
 }
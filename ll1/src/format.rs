@@ -0,0 +1,461 @@
+use super::dialect::Dialect;
+use super::error::GrammarError;
+use super::lexer::{quote_literal, Lexer, Token, TokenKind};
+use super::Grammar;
+
+/// Line width past which a rule gets one alternative per line
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// An alternative of a rule with the comments written around it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Alternative {
+    pub text: String,
+    /// Comments on lines of their own just before the alternative
+    pub leading: Vec<String>,
+    /// Comments following the alternative on its line
+    pub trailing: Vec<String>,
+}
+
+/// A rule ready to be laid out
+#[derive(Debug, Clone)]
+pub(crate) struct Rule {
+    pub name: String,
+    pub alternatives: Vec<Alternative>,
+}
+
+/// A top-level piece of a grammar file
+#[derive(Debug)]
+enum Entry {
+    /// One or more empty lines
+    Blank,
+    /// A comment on lines of its own
+    Comment(String),
+    Directive {
+        text: String,
+        trailing: Vec<String>,
+    },
+    Rule(Rule),
+}
+
+/// Which top-level construct a range of tokens holds
+enum Construct {
+    Directive,
+    /// A legacy first line holding only the start symbol
+    StartLine,
+    Rule,
+}
+
+impl Grammar {
+    /// Rewrites grammar source text in a canonical layout:
+    ///
+    /// - one rule per line, with the `->` of consecutive rules aligned,
+    /// - one alternative per line, each starting with a `|` under the arrow,
+    ///   when the rule does not fit in `width` characters or has comments
+    ///   between its alternatives,
+    /// - one space between symbols, single-quoted literals and `ε` for
+    ///   every spelling of the empty string, including empty alternatives,
+    /// - comments and single blank lines kept where they were.
+    ///
+    /// Rules end with their last alternative, except in the yacc dialect where
+    /// they end with `;`, and a legacy start line becomes `%start`. The source
+    /// must be a valid grammar; formatting the output again changes nothing.
+    pub fn format_source(
+        input: &str,
+        dialect: Dialect,
+        width: usize,
+    ) -> Result<String, GrammarError> {
+        Grammar::read(input, None, dialect)?;
+        let (comments, tokens): (Vec<Token>, Vec<Token>) =
+            Lexer::tokenize_with_comments(input, dialect)?
+                .into_iter()
+                .partition(|token| matches!(token.kind, TokenKind::Comment(_)));
+
+        let epsilons = epsilon_spellings(&tokens);
+        let mut constructs: Vec<(usize, usize, Construct)> = Vec::new();
+        let mut pos = 0;
+        while let Some(token) = tokens.get(pos) {
+            let (end, construct) = match &token.kind {
+                TokenKind::Newline => {
+                    pos += 1;
+                    continue;
+                }
+                TokenKind::Directive(_) => (Self::line_end(&tokens, pos), Construct::Directive),
+                _ if Self::is_rule_head(&tokens, pos) => {
+                    (Self::rule_end(&tokens, pos), Construct::Rule)
+                }
+                _ => (Self::line_end(&tokens, pos), Construct::StartLine),
+            };
+            constructs.push((pos, end, construct));
+            pos = end;
+        }
+
+        // Each entry with the lines it covers in the source
+        let mut entries: Vec<(usize, usize, Entry)> = Vec::new();
+        let mut loose: Vec<&Token> = Vec::new();
+        let lines = |range: &[Token]| {
+            let mut written = range
+                .iter()
+                .filter(|token| token.kind != TokenKind::Newline);
+            let first = written.clone().next().map_or(0, |token| token.span.line);
+            (
+                first,
+                written.next_back().map_or(first, |token| token.span.line),
+            )
+        };
+        // A comment belongs to the last construct that starts before it and
+        // ends on its line or later
+        let mut inner: Vec<Vec<&Token>> = vec![Vec::new(); constructs.len()];
+        for comment in &comments {
+            let at = (comment.span.line, comment.span.column);
+            let owner = constructs.iter().rposition(|(start, end, _)| {
+                let first = &tokens[*start].span;
+                (first.line, first.column) < at && lines(&tokens[*start..*end]).1 >= at.0
+            });
+            match owner {
+                Some(i) => inner[i].push(comment),
+                None => loose.push(comment),
+            }
+        }
+
+        for ((start, end, construct), comments) in constructs.iter().zip(inner) {
+            let range = &tokens[*start..*end];
+            let (first, last) = lines(range);
+            let trailing = comments.iter().map(|c| comment_text(c)).collect();
+            let entry = match construct {
+                Construct::Directive => Entry::Directive {
+                    text: render_directive(range),
+                    trailing,
+                },
+                Construct::StartLine => Entry::Directive {
+                    text: format!("%start {}", render_symbols(&[&range[0]], &epsilons)),
+                    trailing,
+                },
+                Construct::Rule => Entry::Rule(build_rule(range, &comments, &epsilons)),
+            };
+            entries.push((first, last, entry));
+        }
+        for comment in loose {
+            let text = comment_text(comment);
+            let last = comment.span.line + text.matches('\n').count();
+            entries.push((comment.span.line, last, Entry::Comment(text)));
+        }
+        entries.sort_by_key(|(first, _, _)| *first);
+
+        // Blank lines between entries collapse into one
+        let mut laid_out: Vec<Entry> = Vec::new();
+        let mut previous_last = None;
+        for (first, last, entry) in entries {
+            if previous_last.is_some_and(|previous| first > previous + 1) {
+                laid_out.push(Entry::Blank);
+            }
+            previous_last = Some(last);
+            laid_out.push(entry);
+        }
+
+        Ok(layout(&laid_out, dialect, width))
+    }
+}
+
+impl Rule {
+    /// The lines of the rule with its name padded to `name_width` characters,
+    /// on one line when that fits in `width` and no comment is in the way
+    pub(crate) fn lines(&self, name_width: usize, dialect: Dialect, width: usize) -> Vec<String> {
+        let mut lines: Vec<String> = self.alternatives[0].leading.clone();
+        let head = format!("{:<w$} {}", self.name, dialect.definer(), w = name_width);
+        let indent = " ".repeat(name_width + 1);
+        let terminator = if dialect == Dialect::Yacc { " ;" } else { "" };
+        let last = self.alternatives.len() - 1;
+
+        let single_line = format!(
+            "{} {}{}",
+            head,
+            self.alternatives
+                .iter()
+                .map(|alternative| alternative.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" | "),
+            terminator
+        );
+        let comments_in_the_way = self
+            .alternatives
+            .iter()
+            .enumerate()
+            .any(|(i, alternative)| {
+                (i > 0 && !alternative.leading.is_empty())
+                    || (i < last && !alternative.trailing.is_empty())
+            });
+        if !comments_in_the_way && single_line.chars().count() <= width {
+            lines.push(with_comments(
+                single_line,
+                &self.alternatives[last].trailing,
+            ));
+            return lines;
+        }
+
+        for (i, alternative) in self.alternatives.iter().enumerate() {
+            let line = if i == 0 {
+                format!("{} {}", head, alternative.text)
+            } else {
+                for comment in &alternative.leading {
+                    lines.push(format!("{}{}", indent, comment));
+                }
+                format!("{}| {}", indent, alternative.text)
+            };
+            lines.push(with_comments(line, &alternative.trailing));
+        }
+        if dialect == Dialect::Yacc {
+            lines.push(format!("{};", indent));
+        }
+        lines
+    }
+}
+
+/// Lays out `rules` the way `Grammar::format_source` lays out a grammar file
+/// holding only those rules
+pub(crate) fn layout_rules(rules: Vec<Rule>, dialect: Dialect, width: usize) -> String {
+    let entries: Vec<Entry> = rules.into_iter().map(Entry::Rule).collect();
+    layout(&entries, dialect, width)
+}
+
+/// Joins the entries into the formatted text, aligning the arrows of rules
+/// that are not separated by a blank line or a directive
+fn layout(entries: &[Entry], dialect: Dialect, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut run_width = None;
+
+    for (i, entry) in entries.iter().enumerate() {
+        match entry {
+            Entry::Blank => {
+                run_width = None;
+                if !lines.is_empty() {
+                    lines.push(String::new());
+                }
+            }
+            Entry::Comment(text) => lines.push(text.clone()),
+            Entry::Directive { text, trailing } => {
+                run_width = None;
+                lines.push(with_comments(text.clone(), trailing));
+            }
+            Entry::Rule(rule) => {
+                let name_width = *run_width.get_or_insert_with(|| {
+                    entries[i..]
+                        .iter()
+                        .take_while(|entry| matches!(entry, Entry::Rule(_) | Entry::Comment(_)))
+                        .filter_map(|entry| match entry {
+                            Entry::Rule(rule) => Some(rule.name.chars().count()),
+                            _ => None,
+                        })
+                        .max()
+                        .unwrap_or(0)
+                });
+                lines.extend(rule.lines(name_width, dialect, width));
+            }
+        }
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Splits the tokens of a rule into its alternatives, placing each comment
+/// after the alternative it follows on the same line, or else before the
+/// alternative it precedes
+fn build_rule(tokens: &[Token], comments: &[&Token], epsilons: &[String]) -> Rule {
+    let arrow = Grammar::rule_arrow(tokens, 0).unwrap_or(1);
+    let written: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| token.kind != TokenKind::Newline)
+        .collect();
+
+    // The alternative each written token belongs to; a `|` starts the next one
+    let mut owners: Vec<usize> = Vec::new();
+    let mut bodies: Vec<Vec<&Token>> = vec![Vec::new()];
+    let mut depth = 0usize;
+    for (i, token) in written.iter().enumerate() {
+        match token.kind {
+            TokenKind::Pipe if depth == 0 && i > arrow => {
+                bodies.push(Vec::new());
+                owners.push(bodies.len() - 1);
+                continue;
+            }
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        owners.push(bodies.len() - 1);
+        if i > arrow && token.kind != TokenKind::Semicolon {
+            bodies.last_mut().unwrap().push(token);
+        }
+    }
+
+    let mut alternatives: Vec<Alternative> = bodies
+        .iter()
+        .map(|body| Alternative {
+            text: render_symbols(body, epsilons),
+            ..Alternative::default()
+        })
+        .collect();
+    for comment in comments {
+        let at = (comment.span.line, comment.span.column);
+        let before = written
+            .iter()
+            .rposition(|token| (token.span.line, token.span.column) < at);
+        let after = written
+            .iter()
+            .position(|token| (token.span.line, token.span.column) > at);
+        match (before, after) {
+            (Some(i), _) if written[i].span.line == comment.span.line => {
+                alternatives[owners[i]].trailing.push(comment_text(comment))
+            }
+            (_, Some(i)) => alternatives[owners[i]].leading.push(comment_text(comment)),
+            _ => alternatives
+                .last_mut()
+                .unwrap()
+                .trailing
+                .push(comment_text(comment)),
+        }
+    }
+
+    Rule {
+        name: render_symbols(&written[..1], epsilons),
+        alternatives,
+    }
+}
+
+/// Renders symbols and EBNF operators with one space between symbols,
+/// writing `ε` for every spelling of the empty string and for empty
+/// alternatives
+fn render_symbols(tokens: &[&Token], epsilons: &[String]) -> String {
+    let mut text = String::new();
+    // Whether the next piece follows `(`, and whether it starts an alternative of a group
+    let mut glue_next = true;
+    let mut empty = false;
+
+    for token in tokens {
+        let kind = &token.kind;
+        if matches!(kind, TokenKind::Pipe | TokenKind::RParen) && empty {
+            if !glue_next {
+                text.push(' ');
+            }
+            text.push('ε');
+            glue_next = false;
+        }
+
+        let piece = match kind {
+            TokenKind::Symbol(symbol) if epsilons.contains(symbol) => "ε".to_string(),
+            TokenKind::Symbol(symbol) => symbol.clone(),
+            TokenKind::NonTerminal(name) => format!("<{}>", name),
+            TokenKind::Literal(literal) => quote_literal(literal),
+            TokenKind::LParen => "(".to_string(),
+            TokenKind::RParen => ")".to_string(),
+            TokenKind::Star => "*".to_string(),
+            TokenKind::Plus => "+".to_string(),
+            TokenKind::Question => "?".to_string(),
+            TokenKind::Pipe => "|".to_string(),
+            _ => continue,
+        };
+        let glued = glue_next
+            || matches!(
+                kind,
+                TokenKind::RParen | TokenKind::Star | TokenKind::Plus | TokenKind::Question
+            );
+        if !glued {
+            text.push(' ');
+        }
+        text.push_str(&piece);
+        glue_next = *kind == TokenKind::LParen;
+        empty = matches!(kind, TokenKind::LParen | TokenKind::Pipe);
+    }
+
+    if text.is_empty() {
+        text.push('ε');
+    }
+    text
+}
+
+/// `%name` followed by its arguments, literals in single quotes
+fn render_directive(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .filter_map(|token| match &token.kind {
+            TokenKind::Directive(name) => Some(format!("%{}", name)),
+            TokenKind::Symbol(symbol) => Some(symbol.clone()),
+            TokenKind::NonTerminal(name) => Some(format!("<{}>", name)),
+            TokenKind::Literal(literal) => Some(quote_literal(literal)),
+            TokenKind::Newline => None,
+            kind => Some(kind.to_string()),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `ε` and the spellings declared with `%epsilon`
+fn epsilon_spellings(tokens: &[Token]) -> Vec<String> {
+    let mut spellings = vec!["ε".to_string()];
+    for (pos, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Directive("epsilon".to_string()) {
+            continue;
+        }
+        for arg in &tokens[pos + 1..Grammar::line_end(tokens, pos)] {
+            if let TokenKind::Symbol(symbol) = &arg.kind {
+                spellings.push(symbol.clone());
+            }
+        }
+    }
+    spellings
+}
+
+fn comment_text(comment: &Token) -> String {
+    match &comment.kind {
+        TokenKind::Comment(text) => text.trim_end().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// `line` followed by `comments`, two spaces apart
+fn with_comments(line: String, comments: &[String]) -> String {
+    if comments.is_empty() {
+        line
+    } else {
+        format!("{}  {}", line, comments.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DEFAULT_WIDTH;
+    use crate::dialect::Dialect;
+    use crate::Grammar;
+
+    const SOURCE: &str = "%start E\n# expressions\nE->T X   # a sum of terms\nX  ->  \"+\" T X|\nT -> id | '(' E ')'\n\n\n%token id\n";
+
+    fn format(source: &str) -> String {
+        Grammar::format_source(source, Dialect::Arrow, DEFAULT_WIDTH).unwrap()
+    }
+
+    #[test]
+    fn keeps_comments_and_directives() {
+        assert_eq!(
+            format(SOURCE),
+            "%start E\n\
+             # expressions\n\
+             E -> T X  # a sum of terms\n\
+             X -> '+' T X | ε\n\
+             T -> id | '(' E ')'\n\
+             \n\
+             %token id\n"
+        );
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        for width in [DEFAULT_WIDTH, 15] {
+            let once = Grammar::format_source(SOURCE, Dialect::Arrow, width).unwrap();
+            let twice = Grammar::format_source(&once, Dialect::Arrow, width).unwrap();
+            assert_eq!(twice, once);
+        }
+        let narrow = Grammar::format_source(SOURCE, Dialect::Arrow, 15).unwrap();
+        assert!(narrow.contains("\n  | '(' E ')'\n"));
+    }
+}
@@ -1,21 +1,96 @@
-use std::collections::HashSet;
-use std::error::Error;
+use indexmap::{IndexMap, IndexSet};
+use std::fmt;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufRead};
 use std::path::Path;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+use super::dialect::Dialect;
+use super::error::{GrammarError, Span};
+
+/// Productions compare equal when their non-terminal and derivation match,
+/// wherever they were written
+#[derive(Debug, Clone)]
 pub struct Production {
     pub non_terminal: String,
     pub derivation: Vec<String>,
+    /// Where the alternative was written, for productions read from source
+    pub span: Option<Span>,
+}
+
+/// EBNF operators, each lowered by the reader into a helper non-terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EbnfOperator {
+    /// `( α | β )`
+    Group,
+    /// `X?`
+    Optional,
+    /// `X*`
+    Star,
+    /// `X+`
+    Plus,
+}
+
+/// How the kind of a bare symbol is decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SymbolMode {
+    /// Symbols with productions are non-terminals, `%token`s and every other
+    /// symbol are terminals; any identifier may be used, such as `E'` or `IF`
+    #[default]
+    Declared,
+    /// All-uppercase symbols are non-terminals and all-lowercase ones terminals
+    Case,
+}
+
+/// Order in which rules, symbols and their sets are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SymbolOrder {
+    /// The order in which symbols first appear in the source
+    #[default]
+    Source,
+    /// Alphabetical order, independent of how the source is laid out
+    Sorted,
 }
 
-#[derive(Debug)]
+/// Records which EBNF construct a helper non-terminal was generated from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EbnfOrigin {
+    pub operator: EbnfOperator,
+    /// The construct as written in the source, e.g. `(',' Arg)*`
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct Grammar {
     pub productions: Vec<Production>,
-    pub terminals: HashSet<String>,
-    pub non_terminals: HashSet<String>,
+    /// Terminals in the order they are first declared or used
+    pub terminals: IndexSet<String>,
+    /// Non-terminals in the order they are first defined or used, starting
+    /// with the start symbol
+    pub non_terminals: IndexSet<String>,
     pub start_symbol: String,
+    /// Name given by a `%name` directive
+    pub name: Option<String>,
+    /// Helper non-terminals generated from EBNF constructs
+    pub helpers: IndexMap<String, EbnfOrigin>,
+    /// Notation the grammar was read from
+    pub dialect: Dialect,
+    /// How symbols added with `add_production` are classified
+    pub symbol_mode: SymbolMode,
+    /// Order used by the FIRST/FOLLOW computations and the printers
+    pub order: SymbolOrder,
+}
+
+impl EbnfOperator {
+    /// The postfix character written after the operand
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            EbnfOperator::Group => "",
+            EbnfOperator::Optional => "?",
+            EbnfOperator::Star => "*",
+            EbnfOperator::Plus => "+",
+        }
+    }
 }
 
 impl Production {
@@ -24,34 +99,85 @@ impl Production {
         Self {
             non_terminal: non_terminal.to_string(),
             derivation: derivation.iter().map(|s| s.to_string()).collect(),
+            span: None,
         }
     }
 }
 
+/// Displays the production as `A → x y`
+impl fmt::Display for Production {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} → {}", self.non_terminal, self.derivation.join(" "))
+    }
+}
+
+impl PartialEq for Production {
+    fn eq(&self, other: &Self) -> bool {
+        self.non_terminal == other.non_terminal && self.derivation == other.derivation
+    }
+}
+
+impl Eq for Production {}
+
+impl Hash for Production {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.non_terminal.hash(state);
+        self.derivation.hash(state);
+    }
+}
+
 impl Grammar {
     /// Creates a new Grammar with the specified start symbol
     pub fn new(start_symbol: &str) -> Self {
         Grammar {
             productions: Vec::new(),
-            terminals: HashSet::new(),
-            non_terminals: HashSet::new(),
+            terminals: IndexSet::new(),
+            non_terminals: IndexSet::from([start_symbol.to_string()]),
             start_symbol: start_symbol.to_string(),
+            name: None,
+            helpers: IndexMap::new(),
+            dialect: Dialect::default(),
+            symbol_mode: SymbolMode::default(),
+            order: SymbolOrder::default(),
         }
     }
 
     /// Adds a production rule to the grammar
     pub fn add_production(&mut self, non_terminal: &str, derivation: Vec<&str>) {
+        self.add_production_at(non_terminal, derivation, None);
+    }
+
+    /// Adds a production rule read from the source at `span`
+    pub(crate) fn add_production_at(
+        &mut self,
+        non_terminal: &str,
+        derivation: Vec<&str>,
+        span: Option<Span>,
+    ) {
+        self.terminals.shift_remove(non_terminal);
         self.non_terminals.insert(non_terminal.to_string());
 
-        let production = Production::new(non_terminal, derivation.clone());
+        let mut production = Production::new(non_terminal, derivation.clone());
+        production.span = span;
         self.update_symbols(&derivation);
         self.productions.push(production);
     }
 
-    /// Updates the terminal and non-terminal sets based on the derivation
+    /// Registers a terminal, e.g. a quoted literal that does not look like one
+    pub fn add_terminal(&mut self, terminal: &str) {
+        self.terminals.insert(terminal.to_string());
+    }
+
+    /// Updates the terminal and non-terminal sets based on the derivation.
+    ///
+    /// New symbols are terminals until they get a production of their own,
+    /// unless the grammar classifies symbols by case.
     fn update_symbols(&mut self, derivation: &[&str]) {
         for symbol in derivation {
-            if Self::is_non_terminal(symbol) {
+            if self.terminals.contains(*symbol) || self.non_terminals.contains(*symbol) {
+                continue;
+            }
+            if self.symbol_mode == SymbolMode::Case && Self::is_non_terminal(symbol) {
                 self.non_terminals.insert(symbol.to_string());
             } else if *symbol != "ε" {
                 self.terminals.insert(symbol.to_string());
@@ -59,59 +185,46 @@ impl Grammar {
         }
     }
 
-    /// Creates a Grammar from a string representation
-    pub fn from_string(input: &str, start_symbol: &str) -> Result<Self, Box<dyn Error>> {
-        Self::validate_start_symbol(start_symbol)?;
-        let mut grammar = Grammar::new(start_symbol);
-
-        for (line_num, line) in input.lines().enumerate() {
-            if let Some((non_terminal, alternatives)) = Self::parse_production_line(line) {
-                Self::validate_non_terminal(&non_terminal, line_num)?;
+    /// Creates a Grammar from a string representation, detecting its dialect
+    pub fn from_string(input: &str, start_symbol: &str) -> Result<Self, GrammarError> {
+        Self::from_string_in(input, start_symbol, Dialect::detect(input))
+    }
 
-                for alternative in alternatives {
-                    let derivation = Self::parse_derivation(&alternative)?;
-                    Self::validate_derivation(&derivation, line_num)?;
-                    if !derivation.is_empty() {
-                        grammar.add_production(&non_terminal, derivation);
-                    }
-                }
-            }
-        }
+    /// Creates a Grammar from a string written in the given dialect
+    pub fn from_string_in(
+        input: &str,
+        start_symbol: &str,
+        dialect: Dialect,
+    ) -> Result<Self, GrammarError> {
+        Self::read(input, Some(start_symbol), dialect)
+    }
 
-        Ok(grammar)
+    /// Creates a Grammar from a file, detecting its dialect
+    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, GrammarError> {
+        let content = Self::read_file(file_path.as_ref())?;
+        Self::read(&content, None, Dialect::detect(&content))
     }
 
-    /// Creates a Grammar from a file
-    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, Box<dyn Error>> {
-        let content = Self::read_file_content(file_path)?;
-        let start_symbol = Self::extract_start_symbol(&content)?;
-        Grammar::from_string(&content, &start_symbol)
+    /// Creates a Grammar from a file written in the given dialect
+    pub fn from_file_in<P: AsRef<Path>>(
+        file_path: P,
+        dialect: Dialect,
+    ) -> Result<Self, GrammarError> {
+        let content = Self::read_file(file_path.as_ref())?;
+        Self::read(&content, None, dialect)
     }
 }
 
 /// Validators  and helpers
 impl Grammar {
-    fn parse_production_line(line: &str) -> Option<(String, Vec<&str>)> {
-        let line = line.trim();
-        if line.is_empty() {
-            return None;
-        }
-
-        let parts: Vec<&str> = line.split("->").collect();
-        if parts.len() != 2 {
-            return None;
-        }
-
-        let non_terminal = parts[0].trim().to_string();
-        let alternatives = parts[1].trim().split('|').map(|s| s.trim()).collect();
-        Some((non_terminal, alternatives))
+    pub(crate) fn read_file(file_path: &Path) -> Result<String, GrammarError> {
+        Self::read_file_content(file_path).map_err(|source| GrammarError::Io {
+            path: file_path.to_path_buf(),
+            source,
+        })
     }
 
-    fn parse_derivation(alternative: &str) -> Result<Vec<&str>, Box<dyn Error>> {
-        Ok(alternative.split_whitespace().collect())
-    }
-
-    fn read_file_content<P: AsRef<Path>>(file_path: P) -> Result<String, Box<dyn Error>> {
+    fn read_file_content<P: AsRef<Path>>(file_path: P) -> io::Result<String> {
         let file = File::open(file_path)?;
         let reader = io::BufReader::new(file);
         let mut content = String::new();
@@ -122,14 +235,6 @@ impl Grammar {
         Ok(content)
     }
 
-    fn extract_start_symbol(content: &str) -> Result<String, Box<dyn Error>> {
-        let start_symbol = content.lines().next().ok_or("Empty file")?;
-        if !Self::is_non_terminal(start_symbol) {
-            return Err("Start symbol must be uppercase (non-terminal)".into());
-        }
-        Ok(start_symbol.to_string())
-    }
-
     /*
 
     Validators
@@ -141,43 +246,38 @@ impl Grammar {
         symbol == "ε" || Self::is_terminal(symbol) || Self::is_non_terminal(symbol)
     }
 
-    fn is_terminal(symbol: &str) -> bool {
+    pub(crate) fn is_terminal(symbol: &str) -> bool {
         symbol.chars().all(|c| c.is_lowercase())
     }
 
-    fn is_non_terminal(symbol: &str) -> bool {
+    pub(crate) fn is_non_terminal(symbol: &str) -> bool {
         symbol.chars().all(|c| c.is_uppercase())
     }
 
-    fn validate_start_symbol(start_symbol: &str) -> Result<(), Box<dyn Error>> {
+    pub(crate) fn validate_start_symbol(
+        start_symbol: &str,
+        span: Option<Span>,
+    ) -> Result<(), GrammarError> {
         if !Self::is_non_terminal(start_symbol) {
-            return Err("Start symbol must be uppercase (non-terminal)".into());
+            return Err(GrammarError::InvalidStartSymbol {
+                symbol: start_symbol.to_string(),
+                reason: "must be uppercase (non-terminal)".to_string(),
+                span,
+            });
         }
         Ok(())
     }
 
-    fn validate_non_terminal(non_terminal: &str, line_num: usize) -> Result<(), Box<dyn Error>> {
+    pub(crate) fn validate_non_terminal(
+        non_terminal: &str,
+        span: Span,
+    ) -> Result<(), GrammarError> {
         if !Self::is_non_terminal(non_terminal) {
-            return Err(format!(
-                "Error on line {}: Non-terminal '{}' must be uppercase",
-                line_num + 1,
-                non_terminal
-            )
-            .into());
-        }
-        Ok(())
-    }
-
-    fn validate_derivation(derivation: &[&str], line_num: usize) -> Result<(), Box<dyn Error>> {
-        for symbol in derivation {
-            if !Self::is_valid_symbol(symbol) {
-                return Err(format!(
-                    "Error on line {}: Invalid symbol '{}' in derivation",
-                    line_num + 1,
-                    symbol
-                )
-                .into());
-            }
+            return Err(GrammarError::InvalidNonTerminal {
+                symbol: non_terminal.to_string(),
+                reason: "must be uppercase".to_string(),
+                span,
+            });
         }
         Ok(())
     }
@@ -0,0 +1,345 @@
+use std::path::Path;
+
+use super::{Import, Rewriter};
+use crate::error::GrammarError;
+use crate::Grammar;
+
+/// Keywords that may precede a rule name
+const RULE_MODIFIERS: [&str; 3] = ["public", "private", "protected"];
+
+impl Grammar {
+    /// Imports the parser rules of an ANTLR4 grammar.
+    ///
+    /// Lexer rules (names starting with an uppercase letter) are dropped and
+    /// their names become terminals, as do literals such as `'+'`. EBNF
+    /// suffixes are lowered by the grammar reader; alternative labels
+    /// (`# Add`), element labels (`lhs=expr`, `ids+=ID`) and `EOF` are removed.
+    /// Every action, semantic predicate, element option, set negation `~x`,
+    /// wildcard `.` and lexer mode that gets dropped is reported as a warning.
+    pub fn from_antlr(input: &str) -> Result<Import, GrammarError> {
+        let mut rewriter = Rewriter::new(input);
+        rewriter.antlr_grammar();
+        rewriter.read(None)
+    }
+
+    /// Imports the parser rules of an ANTLR4 `.g4` file
+    pub fn from_antlr_file<P: AsRef<Path>>(file_path: P) -> Result<Import, GrammarError> {
+        let content = Self::read_file(file_path.as_ref())?;
+        Self::from_antlr(&content)
+    }
+}
+
+impl Rewriter {
+    /// Rewrites the top-level statements of an ANTLR grammar
+    fn antlr_grammar(&mut self) {
+        while !self.is_done() {
+            let space = self.space_len(0);
+            if space > 0 {
+                self.keep(space);
+                continue;
+            }
+            let comment = self.comment_len(self.pos);
+            if comment > 0 {
+                self.keep(comment);
+                continue;
+            }
+
+            let c = self.peek(0).unwrap_or_default();
+            if c == '@' {
+                // A named action such as `@header { ... }` or `@parser::members { ... }`
+                let n = self.word_len(1, &[':']);
+                self.warn(
+                    format!(
+                        "Action {} dropped",
+                        self.chars[self.pos..self.pos + 1 + n]
+                            .iter()
+                            .collect::<String>()
+                    ),
+                    1 + n,
+                );
+                self.drop(1 + n);
+                self.drop_block();
+                continue;
+            }
+            if !(c.is_alphabetic() || c == '_') {
+                self.keep(1);
+                continue;
+            }
+
+            let n = self.word_len(0, &[]);
+            let word: String = self.chars[self.pos..self.pos + n].iter().collect();
+            match word.as_str() {
+                "grammar" | "parser" | "lexer" => self.antlr_header(),
+                "import" => self.drop_statement(),
+                "mode" => {
+                    let end = self.statement_len();
+                    let mode: String = self.chars[self.pos + n..self.pos + end].iter().collect();
+                    let mode = mode.trim().trim_end_matches(';').trim();
+                    self.warn(format!("Lexer mode '{}' dropped", mode), end);
+                    self.drop(end);
+                }
+                "options" | "tokens" | "channels" => {
+                    self.drop(n);
+                    self.drop_block();
+                }
+                "catch" | "finally" => {
+                    self.warn(format!("Exception handler '{}' dropped", word), n);
+                    self.drop(n);
+                    let space = self.space_len(0);
+                    self.drop(space);
+                    if self.peek(0) == Some('[') {
+                        self.drop_bracketed();
+                    }
+                    self.drop_block();
+                }
+                "fragment" => self.drop_statement(),
+                _ if RULE_MODIFIERS.contains(&word.as_str()) => self.drop(n),
+                _ if c.is_uppercase() => self.drop_statement(),
+                _ => self.antlr_parser_rule(n),
+            }
+        }
+    }
+
+    /// Turns `parser grammar Name;` into `%name Name`
+    fn antlr_header(&mut self) {
+        let end = self.statement_len();
+        let header: String = self.chars[self.pos..self.pos + end].iter().collect();
+        let name = header
+            .trim_end_matches(';')
+            .split_whitespace()
+            .last()
+            .unwrap_or_default()
+            .to_string();
+        self.replace(end, &format!("%name {}", name));
+    }
+
+    /// Rewrites a parser rule whose name is `n` characters long
+    fn antlr_parser_rule(&mut self, n: usize) {
+        self.keep(n);
+
+        // Arguments, return values, locals and rule actions before the `:`
+        while !self.is_done() && self.peek(0) != Some(':') {
+            let space = self.space_len(0);
+            if space > 0 {
+                self.keep(space);
+                continue;
+            }
+            match self.peek(0) {
+                Some('[') => self.drop_bracketed(),
+                Some('@') => {
+                    let n = self.word_len(1, &[]);
+                    self.warn(
+                        format!(
+                            "Rule action {} dropped",
+                            self.chars[self.pos..self.pos + 1 + n]
+                                .iter()
+                                .collect::<String>()
+                        ),
+                        1 + n,
+                    );
+                    self.drop(1 + n);
+                    self.drop_block();
+                }
+                Some('{') => self.drop_block(),
+                _ => {
+                    // `returns`, `locals`, `throws` and `options`
+                    let n = self.word_len(0, &['.']).max(1);
+                    self.drop(n);
+                }
+            }
+        }
+
+        while !self.is_done() {
+            let comment = self.comment_len(self.pos);
+            if comment > 0 {
+                self.keep(comment);
+                continue;
+            }
+
+            let c = self.peek(0).unwrap_or_default();
+            match c {
+                ';' => {
+                    self.keep(1);
+                    return;
+                }
+                '\'' => self.keep(self.quoted_len(self.pos)),
+                '{' => {
+                    let n = self.braced_len(self.pos);
+                    if self.peek(n) == Some('?') {
+                        self.warn("Semantic predicate dropped".to_string(), n + 1);
+                        self.drop(n + 1);
+                    } else {
+                        self.warn("Action dropped".to_string(), n);
+                        self.drop(n);
+                    }
+                }
+                '<' => {
+                    let n = self
+                        .find_from(self.pos, ">")
+                        .map_or(1, |end| end + 1 - self.pos);
+                    let option: String = self.chars[self.pos..self.pos + n].iter().collect();
+                    self.warn(format!("Element option {} dropped", option), n);
+                    self.drop(n);
+                }
+                // Alternative labels such as `# Add`
+                '#' => {
+                    let space = self.space_len(1);
+                    let n = 1 + space + self.word_len(1 + space, &[]);
+                    self.drop(n);
+                }
+                // Elements our notation has no equivalent for
+                '~' | '.' => {
+                    let n = self.unsupported_element_len();
+                    let element: String = self.chars[self.pos..self.pos + n].iter().collect();
+                    let what = if c == '~' { "Set negation" } else { "Wildcard" };
+                    self.warn(format!("{} {} dropped", what, element.trim_end()), n);
+                    self.drop(n);
+                }
+                '*' | '+' | '?' if self.peek(1) == Some('?') => {
+                    self.keep(1);
+                    self.warn("Non-greedy operator treated as greedy".to_string(), 1);
+                    self.drop(1);
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let n = self.word_len(0, &[]);
+                    let space = self.space_len(n);
+                    let label = match (self.peek(n + space), self.peek(n + space + 1)) {
+                        (Some('='), _) => Some(n + space + 1),
+                        (Some('+'), Some('=')) => Some(n + space + 2),
+                        _ => None,
+                    };
+                    match label {
+                        Some(label) => self.drop(label),
+                        None if self.chars[self.pos..self.pos + n] == ['E', 'O', 'F'] => {
+                            self.drop(n)
+                        }
+                        None => self.keep(n),
+                    }
+                }
+                _ => self.keep(1),
+            }
+        }
+    }
+
+    /// Number of characters in the `.` wildcard or the `~` set negation and
+    /// its operand at the current position, with any EBNF suffix after them
+    fn unsupported_element_len(&self) -> usize {
+        let mut n = 1;
+        if self.peek(0) == Some('~') {
+            n += self.space_len(n);
+            n += match self.peek(n) {
+                Some('\'' | '"') => self.quoted_len(self.pos + n),
+                Some('(') => self.parenthesized_len(self.pos + n),
+                Some('[') => self.bracketed_len(self.pos + n),
+                _ => self.word_len(n, &[]),
+            };
+        }
+        if matches!(self.peek(n), Some('*' | '+' | '?')) {
+            n += 1;
+            if self.peek(n) == Some('?') {
+                n += 1;
+            }
+        }
+        n
+    }
+
+    /// Number of characters in the `( ... )` at `start`, skipping literals
+    fn parenthesized_len(&self, start: usize) -> usize {
+        let mut end = start;
+        let mut depth = 0;
+        while let Some(&c) = self.chars.get(end) {
+            match c {
+                '\'' | '"' => {
+                    end += self.quoted_len(end);
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            end += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+        end - start
+    }
+
+    /// Number of characters up to and including the `;` ending the statement
+    /// at the current position, skipping literals, blocks and comments
+    fn statement_len(&self) -> usize {
+        let mut end = self.pos;
+        while let Some(&c) = self.chars.get(end) {
+            let comment = self.comment_len(end);
+            if comment > 0 {
+                end += comment;
+                continue;
+            }
+            match c {
+                ';' => return end + 1 - self.pos,
+                '\'' | '"' => end += self.quoted_len(end),
+                '{' => end += self.braced_len(end),
+                '[' => end += self.bracketed_len(end),
+                _ => end += 1,
+            }
+        }
+        end - self.pos
+    }
+
+    fn drop_statement(&mut self) {
+        self.drop(self.statement_len());
+    }
+
+    /// Drops blanks and the `{ ... }` block after them, if there is one
+    fn drop_block(&mut self) {
+        let space = self.space_len(0);
+        if self.peek(space) == Some('{') {
+            self.drop(space);
+            self.drop(self.braced_len(self.pos));
+        }
+    }
+
+    fn drop_bracketed(&mut self) {
+        self.drop(self.bracketed_len(self.pos));
+    }
+
+    /// Number of characters in the `[ ... ]` at `start`, such as rule arguments
+    /// or a lexer character set, which may hold escaped brackets
+    fn bracketed_len(&self, start: usize) -> usize {
+        let mut end = start + 1;
+        while let Some(&c) = self.chars.get(end) {
+            end += 1;
+            match c {
+                '\\' => end += 1,
+                ']' => break,
+                _ => {}
+            }
+        }
+        end.min(self.chars.len()) - start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    #[test]
+    fn drops_set_negations_and_wildcards_with_a_warning() {
+        let source = "grammar G;\nr : 'a' ~('b' | ')') c ;\nc : .*? 'd' | ~ID ;\n";
+        let import = Grammar::from_antlr(source).unwrap();
+        let derivations: Vec<String> = import
+            .grammar
+            .productions
+            .iter()
+            .map(|p| p.derivation.join(" "))
+            .collect();
+        assert_eq!(derivations, ["a c", "d", "ε"]);
+
+        let warnings: Vec<String> = import.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("line 2, column 9"));
+        assert!(warnings[0].contains("Set negation ~('b' | ')') dropped"));
+        assert!(warnings[1].contains("Wildcard .*? dropped"));
+        assert!(warnings[2].contains("Set negation ~ID dropped"));
+    }
+}
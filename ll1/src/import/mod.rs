@@ -0,0 +1,257 @@
+pub mod antlr;
+pub mod yacc;
+
+use super::dialect::Dialect;
+use super::error::{Diagnostic, GrammarError, Span};
+use super::Grammar;
+
+/// A grammar imported from another tool's format, with warnings about the
+/// constructs that were dropped or could not be represented
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub grammar: Grammar,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Rewrites foreign grammar source into one of our dialects.
+///
+/// Dropped text is replaced with spaces and newlines are always kept, so
+/// lines in the rewritten text match the original. Columns match too, except
+/// after a replacement longer than the text it replaces; `Rewriter::read`
+/// maps those back, so errors and production spans point at the right place
+/// in the imported file.
+struct Rewriter {
+    chars: Vec<char>,
+    out: Vec<char>,
+    pos: usize,
+    warnings: Vec<Diagnostic>,
+    /// The replacements longer than the text they replace, in order
+    shifts: Vec<Shift>,
+}
+
+/// A replacement that pushed the rest of its line to the right
+#[derive(Debug, Clone, Copy)]
+struct Shift {
+    line: usize,
+    /// Column of the replacement in the rewritten text
+    column: usize,
+    /// Length of the replaced text
+    replaced: usize,
+    /// Length of the replacement
+    width: usize,
+}
+
+impl Rewriter {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            out: Vec::new(),
+            pos: 0,
+            warnings: Vec::new(),
+            shifts: Vec::new(),
+        }
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).copied()
+    }
+
+    fn at(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(n, c)| self.peek(n) == Some(c))
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    /// Copies the next `n` characters unchanged
+    fn keep(&mut self, n: usize) {
+        let end = (self.pos + n).min(self.chars.len());
+        self.out.extend_from_slice(&self.chars[self.pos..end]);
+        self.pos = end;
+    }
+
+    /// Replaces the next `n` characters with spaces, keeping newlines
+    fn drop(&mut self, n: usize) {
+        let end = (self.pos + n).min(self.chars.len());
+        for &c in &self.chars[self.pos..end] {
+            self.out.push(if c == '\n' { '\n' } else { ' ' });
+        }
+        self.pos = end;
+    }
+
+    /// Replaces the next `n` characters with `text`, padded with spaces when
+    /// shorter and recorded as a shift when longer
+    fn replace(&mut self, n: usize, text: &str) {
+        let width = text.chars().count();
+        if width > n {
+            let line = self.out.iter().filter(|&&c| c == '\n').count() + 1;
+            let column = self.out.len()
+                - self
+                    .out
+                    .iter()
+                    .rposition(|&c| c == '\n')
+                    .map_or(0, |i| i + 1)
+                + 1;
+            self.shifts.push(Shift {
+                line,
+                column,
+                replaced: n,
+                width,
+            });
+        }
+        self.out.extend(text.chars());
+        self.out
+            .extend(std::iter::repeat_n(' ', n.saturating_sub(width)));
+        self.pos = (self.pos + n).min(self.chars.len());
+    }
+
+    /// Drops everything up to the end of the input
+    fn drop_rest(&mut self) {
+        self.drop(self.chars.len() - self.pos);
+    }
+
+    /// Number of characters in the quoted string or character literal at
+    /// `start`, quotes included
+    fn quoted_len(&self, start: usize) -> usize {
+        let quote = self.chars[start];
+        let mut end = start + 1;
+        while let Some(&c) = self.chars.get(end) {
+            end += 1;
+            if c == '\\' {
+                end += 1;
+            } else if c == quote || c == '\n' {
+                break;
+            }
+        }
+        end.min(self.chars.len()) - start
+    }
+
+    /// Number of characters in the comment at `start`, or 0 when there is none
+    fn comment_len(&self, start: usize) -> usize {
+        let end = match self.chars.get(start..start + 2) {
+            Some(['/', '*']) => self.find_from(start + 2, "*/").map(|end| end + 2),
+            Some(['/', '/']) => self.find_from(start, "\n"),
+            _ => return 0,
+        };
+        end.unwrap_or(self.chars.len()) - start
+    }
+
+    /// Number of characters in the `{ ... }` block at `start`, skipping
+    /// braces inside strings, character literals and comments
+    fn braced_len(&self, start: usize) -> usize {
+        let mut end = start;
+        let mut depth = 0;
+        while let Some(&c) = self.chars.get(end) {
+            let comment = self.comment_len(end);
+            if comment > 0 {
+                end += comment;
+                continue;
+            }
+            match c {
+                '"' | '\'' => end += self.quoted_len(end),
+                '{' => {
+                    depth += 1;
+                    end += 1;
+                }
+                '}' => {
+                    depth -= 1;
+                    end += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => end += 1,
+            }
+        }
+        end - start
+    }
+
+    /// Number of characters in the identifier `offset` characters ahead,
+    /// which may also contain the `extra` characters
+    fn word_len(&self, offset: usize, extra: &[char]) -> usize {
+        let mut n = 0;
+        while let Some(c) = self.peek(offset + n) {
+            if !(c.is_alphanumeric() || c == '_' || extra.contains(&c)) {
+                break;
+            }
+            n += 1;
+        }
+        n
+    }
+
+    /// Number of whitespace characters, newlines included, `offset` characters ahead
+    fn space_len(&self, offset: usize) -> usize {
+        let mut n = 0;
+        while self.peek(offset + n).is_some_and(char::is_whitespace) {
+            n += 1;
+        }
+        n
+    }
+
+    fn find_from(&self, start: usize, text: &str) -> Option<usize> {
+        let needle: Vec<char> = text.chars().collect();
+        (start..self.chars.len()).find(|&i| self.chars[i..].starts_with(&needle))
+    }
+
+    /// Span of the `n` characters at the current position
+    fn span(&self, n: usize) -> Span {
+        let before = &self.chars[..self.pos];
+        let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = self.pos - before.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1) + 1;
+        Span::new(line, column, n)
+    }
+
+    fn warn(&mut self, message: String, n: usize) {
+        let span = self.span(n);
+        self.warnings.push(Diagnostic::warning(message, Some(span)));
+    }
+
+    /// Reads the rewritten text in the yacc dialect, with the spans of errors
+    /// and productions mapped back to the imported file
+    fn read(self, start_symbol: Option<&str>) -> Result<Import, GrammarError> {
+        let text: String = self.out.iter().collect();
+        let mut grammar =
+            Grammar::read(&text, start_symbol, Dialect::Yacc).map_err(|mut error| {
+                if let Some(span) = error.span_mut() {
+                    *span = self.original_span(*span);
+                }
+                error
+            })?;
+        for production in &mut grammar.productions {
+            production.span = production.span.map(|span| self.original_span(span));
+        }
+        Ok(Import {
+            grammar,
+            warnings: self.warnings,
+        })
+    }
+
+    /// The span in the imported file of `span` in the rewritten text
+    fn original_span(&self, span: Span) -> Span {
+        let start = self.original_column(span.line, span.column);
+        let end = self.original_column(span.line, span.column + span.length.max(1) - 1) + 1;
+        Span::new(
+            span.line,
+            start,
+            if span.length == 0 { 0 } else { end - start },
+        )
+    }
+
+    /// The column in the imported file of `column` in the rewritten text;
+    /// columns inside a replacement map onto the text it replaced
+    fn original_column(&self, line: usize, column: usize) -> usize {
+        let mut shifted = 0;
+        for shift in self.shifts.iter().filter(|shift| shift.line == line) {
+            if column >= shift.column + shift.width {
+                shifted += shift.width - shift.replaced;
+            } else if column >= shift.column {
+                let inside = (column - shift.column).min(shift.replaced - 1);
+                return shift.column - shifted + inside;
+            }
+        }
+        column - shifted
+    }
+}
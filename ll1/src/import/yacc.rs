@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use indexmap::IndexMap;
+
+use super::{Import, Rewriter};
+use crate::error::{Diagnostic, GrammarError, Span};
+use crate::lexer::quote_literal;
+use crate::Grammar;
+
+/// Declarations that only tune an LR parser's conflict resolution
+const PRECEDENCE_DECLARATIONS: [&str; 4] = ["left", "right", "nonassoc", "precedence"];
+
+/// What the declarations section says about the rules
+#[derive(Debug, Default)]
+struct Declarations {
+    start: Option<String>,
+    /// Names declared with `%token`, in order, with where they were declared
+    tokens: IndexMap<String, Span>,
+    /// String aliases of tokens, as in `%token LE "<="`, keyed by the quoted text
+    aliases: HashMap<String, String>,
+}
+
+impl Grammar {
+    /// Imports the rules section of a Bison/Yacc file.
+    ///
+    /// Symbols declared with `%token`, even those no rule uses, and every
+    /// other symbol without rules become terminals; a `%token` that has rules
+    /// stays a non-terminal and is reported as a warning. Character literals such as `'+'` become quoted
+    /// terminals, escaped ones such as `'\n'` become terminals spelled like
+    /// the escape, `\n`, and string aliases such as `"<="` are replaced by
+    /// their token.
+    /// Semantic actions, the prologue and the epilogue are ignored. Precedence
+    /// declarations and `%prec` cannot be represented and are reported as
+    /// warnings. A file without `%%` is read as a rules section on its own.
+    pub fn from_yacc(input: &str) -> Result<Import, GrammarError> {
+        let mut rewriter = Rewriter::new(input);
+        let declarations = if input.lines().any(|line| line.trim_end() == "%%") {
+            rewriter.yacc_declarations()
+        } else {
+            Declarations::default()
+        };
+        rewriter.yacc_rules(&declarations.aliases);
+        let mut import = rewriter.read(declarations.start.as_deref())?;
+        for (token, span) in declarations.tokens {
+            if import.grammar.non_terminals.contains(&token) {
+                import.warnings.push(Diagnostic::warning(
+                    format!(
+                        "%token {} also has rules; it is read as a non-terminal",
+                        token
+                    ),
+                    Some(span),
+                ));
+            } else {
+                import.grammar.add_terminal(&token);
+            }
+        }
+        Ok(import)
+    }
+
+    /// Imports the rules section of a Bison/Yacc `.y` file
+    pub fn from_yacc_file<P: AsRef<Path>>(file_path: P) -> Result<Import, GrammarError> {
+        let content = Self::read_file(file_path.as_ref())?;
+        Self::from_yacc(&content)
+    }
+}
+
+impl Rewriter {
+    /// Reads and drops the declarations section, up to and including its `%%`
+    fn yacc_declarations(&mut self) -> Declarations {
+        let mut declarations = Declarations::default();
+        let mut directive = String::new();
+        let mut last_token: Option<String> = None;
+
+        while !self.is_done() {
+            if self.at("%%") {
+                self.drop(2);
+                break;
+            }
+            if self.at("%{") {
+                let end = self
+                    .find_from(self.pos, "%}")
+                    .map_or(self.chars.len(), |end| end + 2);
+                self.drop(end - self.pos);
+                continue;
+            }
+            let comment = self.comment_len(self.pos);
+            if comment > 0 {
+                self.drop(comment);
+                continue;
+            }
+
+            let c = self.peek(0).unwrap_or_default();
+            match c {
+                '%' => {
+                    let n = self.word_len(1, &['-']);
+                    directive = self.chars[self.pos + 1..self.pos + 1 + n].iter().collect();
+                    last_token = None;
+                    if PRECEDENCE_DECLARATIONS.contains(&directive.as_str()) {
+                        self.warn(
+                            format!(
+                                "%{} cannot be represented; precedence and associativity are ignored",
+                                directive
+                            ),
+                            n + 1,
+                        );
+                    }
+                    self.drop(n + 1);
+                }
+                '{' => self.drop(self.braced_len(self.pos)),
+                '<' => {
+                    // A type tag such as `<int>`
+                    let n = self
+                        .find_from(self.pos, ">")
+                        .map_or(1, |end| end + 1 - self.pos);
+                    self.drop(n);
+                }
+                '"' => {
+                    let n = self.quoted_len(self.pos);
+                    if directive == "token" {
+                        if let Some(token) = &last_token {
+                            let alias: String = self.chars[self.pos..self.pos + n].iter().collect();
+                            declarations.aliases.insert(alias, token.clone());
+                        }
+                    }
+                    self.drop(n);
+                }
+                '\'' => self.drop(self.quoted_len(self.pos)),
+                c if c.is_alphabetic() || c == '_' => {
+                    let n = self.word_len(0, &['.', '-']);
+                    let word: String = self.chars[self.pos..self.pos + n].iter().collect();
+                    match directive.as_str() {
+                        "start" => declarations.start = Some(word),
+                        "token" => {
+                            declarations.tokens.insert(word.clone(), self.span(n));
+                            last_token = Some(word);
+                        }
+                        _ => {}
+                    }
+                    self.drop(n);
+                }
+                _ => self.drop(1),
+            }
+        }
+
+        declarations
+    }
+
+    /// Rewrites the rules section into the yacc dialect and drops the epilogue
+    fn yacc_rules(&mut self, aliases: &HashMap<String, String>) {
+        while !self.is_done() {
+            if self.at("%%") {
+                break;
+            }
+            let comment = self.comment_len(self.pos);
+            if comment > 0 {
+                self.keep(comment);
+                continue;
+            }
+
+            let c = self.peek(0).unwrap_or_default();
+            match c {
+                '\'' => {
+                    let n = self.quoted_len(self.pos);
+                    let literal: String =
+                        self.chars[self.pos + 1..self.pos + n - 1].iter().collect();
+                    if literal.starts_with('\\')
+                        && !["\\\\", "\\'", "\\\""].contains(&literal.as_str())
+                    {
+                        // `'\n'` becomes the printable terminal `\n`
+                        self.replace(n, &quote_literal(&literal));
+                    } else {
+                        self.keep(n);
+                    }
+                }
+                '"' => {
+                    let n = self.quoted_len(self.pos);
+                    let text: String = self.chars[self.pos..self.pos + n].iter().collect();
+                    match aliases.get(&text) {
+                        Some(token) => self.replace(n, token),
+                        None => self.keep(n),
+                    }
+                }
+                // Semantic actions, including mid-rule ones
+                '{' => self.drop(self.braced_len(self.pos)),
+                // Named references such as `exp[left]`
+                '[' => {
+                    let n = self
+                        .find_from(self.pos, "]")
+                        .map_or(1, |end| end + 1 - self.pos);
+                    self.drop(n);
+                }
+                '%' if self.at("%empty") => self.replace(6, "ε"),
+                '%' if self.at("%?{") => {
+                    self.warn("Semantic predicate dropped".to_string(), 2);
+                    self.drop(2);
+                    self.drop(self.braced_len(self.pos));
+                }
+                '%' => {
+                    let name_len = self.word_len(1, &['-']);
+                    let name: String = self.chars[self.pos + 1..self.pos + 1 + name_len]
+                        .iter()
+                        .collect();
+                    let arg_len = self.argument_len(1 + name_len);
+                    let message = match name.as_str() {
+                        "prec" => "%prec cannot be represented; the rule keeps its own precedence",
+                        "dprec" | "merge" => "GLR disambiguation is not supported and was dropped",
+                        _ => "Unknown rule directive dropped",
+                    };
+                    self.warn(message.to_string(), 1 + name_len);
+                    self.drop(1 + name_len + arg_len);
+                }
+                _ => self.keep(1),
+            }
+        }
+        self.drop_rest();
+    }
+
+    /// Number of characters, leading blanks included, in the single symbol,
+    /// literal or `<tag>` argument `offset` characters ahead
+    fn argument_len(&self, offset: usize) -> usize {
+        let mut n = offset;
+        while matches!(self.peek(n), Some(' ' | '\t')) {
+            n += 1;
+        }
+        let start = self.pos + n;
+        let len = match self.peek(n) {
+            Some('\'' | '"') => self.quoted_len(start),
+            Some('<') => self.find_from(start, ">").map_or(1, |end| end + 1 - start),
+            _ => self.word_len(n, &['.', '-']),
+        };
+        n + len - offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    const SOURCE: &str = "%token NUM UNUSED\n%token expr\n%%\nexpr : NUM | '(' expr ')' ;\n";
+
+    #[test]
+    fn declared_tokens_are_terminals() {
+        let import = Grammar::from_yacc(SOURCE).unwrap();
+        assert!(import.grammar.terminals.contains("NUM"));
+        assert!(import.grammar.terminals.contains("UNUSED"));
+    }
+
+    #[test]
+    fn warns_about_a_token_with_rules() {
+        let import = Grammar::from_yacc(SOURCE).unwrap();
+        assert!(import.grammar.non_terminals.contains("expr"));
+        assert_eq!(import.warnings.len(), 1);
+        let span = import.warnings[0].span.unwrap();
+        assert_eq!((span.line, span.column), (2, 8));
+    }
+
+    #[test]
+    fn errors_after_a_longer_alias_point_at_the_original_column() {
+        let source = "%token PLUS \"+\"\n%%\ne : \"+\" NUM ) ;\n";
+        let error = Grammar::from_yacc(source).unwrap_err();
+        let span = error.span().unwrap();
+        assert_eq!((span.line, span.column, span.length), (3, 13, 1));
+    }
+
+    #[test]
+    fn escaped_character_literals_become_printable_terminals() {
+        let source = "%%\nline : 'a' '\\n' | '\\'' ;\n";
+        let import = Grammar::from_yacc(source).unwrap();
+        let derivations: Vec<&Vec<String>> = import
+            .grammar
+            .productions
+            .iter()
+            .map(|p| &p.derivation)
+            .collect();
+        assert_eq!(derivations, [&vec!["a", "\\n"], &vec!["'"]]);
+    }
+}
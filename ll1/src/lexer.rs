@@ -0,0 +1,496 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::dialect::Dialect;
+use super::error::{GrammarError, Span};
+
+/// Kinds of tokens found in the grammar notation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A bare symbol such as `A`, `id` or `ε`
+    Symbol(String),
+    /// A BNF non-terminal such as `<expr>`, stored without the brackets
+    NonTerminal(String),
+    /// A quoted literal terminal such as `'+'` or `":="`, with escapes resolved
+    Literal(String),
+    /// The `->` separating a non-terminal from its alternatives,
+    /// spelled `→`, `::=` or `:` in other dialects
+    Arrow,
+    /// The `|` separating alternatives
+    Pipe,
+    /// A header directive such as `%start`, stored without the `%`
+    Directive(String),
+    /// `(` opening an EBNF group
+    LParen,
+    /// `)` closing an EBNF group
+    RParen,
+    /// The EBNF `*` (zero or more) operator
+    Star,
+    /// The EBNF `+` (one or more) operator
+    Plus,
+    /// The EBNF `?` (optional) operator
+    Question,
+    /// `;` ending a rule that spans several lines
+    Semicolon,
+    /// End of a physical line
+    Newline,
+    /// A `#`, `//` or `/* ... */` comment, with its markers; only produced
+    /// by `Lexer::tokenize_with_comments`
+    Comment(String),
+}
+
+/// A token together with its location in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits grammar source text into tokens
+pub struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    dialect: Dialect,
+    /// Whether comments become tokens instead of being skipped
+    keep_comments: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str, dialect: Dialect) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+            dialect,
+            keep_comments: false,
+        }
+    }
+
+    /// Tokenizes the whole input
+    pub fn tokenize(input: &str, dialect: Dialect) -> Result<Vec<Token>, GrammarError> {
+        let mut lexer = Lexer::new(input, dialect);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    /// Tokenizes the whole input, keeping comments as `Comment` tokens
+    pub fn tokenize_with_comments(
+        input: &str,
+        dialect: Dialect,
+    ) -> Result<Vec<Token>, GrammarError> {
+        let mut lexer = Lexer::new(input, dialect);
+        lexer.keep_comments = true;
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    /// Returns the next token, or `None` at the end of the input.
+    /// Comments starting with `#` or `//` run to the end of the line and are skipped,
+    /// as are `/* ... */` comments.
+    pub fn next_token(&mut self) -> Result<Option<Token>, GrammarError> {
+        loop {
+            let comment_start = match self.chars.peek().copied() {
+                Some('#') => true,
+                Some('/') => matches!(self.lookahead(1), Some('/') | Some('*')),
+                _ => false,
+            };
+            if comment_start && self.keep_comments {
+                return Ok(Some(self.comment()));
+            }
+            match self.chars.peek().copied() {
+                Some('#') => self.skip_comment(),
+                Some('/') if self.lookahead(1) == Some('/') => self.skip_comment(),
+                Some('/') if self.lookahead(1) == Some('*') => self.skip_block_comment(),
+                Some(c) if c != '\n' && c.is_whitespace() => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+
+        let (line, column) = (self.line, self.column);
+        let c = match self.chars.peek() {
+            Some(&c) => c,
+            None => return Ok(None),
+        };
+
+        let kind = match c {
+            '\n' => {
+                self.bump();
+                return Ok(Some(Token {
+                    kind: TokenKind::Newline,
+                    span: Span::new(line, column, 1),
+                }));
+            }
+            '|' | '(' | ')' | '*' | '+' | '?' | ';' => {
+                self.bump();
+                match c {
+                    '|' => TokenKind::Pipe,
+                    ';' => TokenKind::Semicolon,
+                    '(' => TokenKind::LParen,
+                    ')' => TokenKind::RParen,
+                    '*' => TokenKind::Star,
+                    '+' => TokenKind::Plus,
+                    _ => TokenKind::Question,
+                }
+            }
+            '-' if self.lookahead(1) == Some('>') && !self.dialect.kinds_by_syntax() => {
+                self.bump();
+                self.bump();
+                TokenKind::Arrow
+            }
+            '→' if !self.dialect.kinds_by_syntax() => {
+                self.bump();
+                TokenKind::Arrow
+            }
+            ':' if self.dialect == Dialect::Bnf
+                && self.lookahead(1) == Some(':')
+                && self.lookahead(2) == Some('=') =>
+            {
+                self.bump();
+                self.bump();
+                self.bump();
+                TokenKind::Arrow
+            }
+            ':' if self.dialect == Dialect::Yacc => {
+                self.bump();
+                TokenKind::Arrow
+            }
+            '<' if self.dialect == Dialect::Bnf => {
+                TokenKind::NonTerminal(self.bracketed(line, column)?)
+            }
+            '%' if self.lookahead(1).is_some_and(Self::is_symbol_start) => {
+                self.bump();
+                TokenKind::Directive(self.symbol())
+            }
+            '\'' | '"' => TokenKind::Literal(self.literal(line, column)?),
+            c if Self::is_symbol_start(c) => TokenKind::Symbol(self.symbol()),
+            character => {
+                return Err(GrammarError::UnexpectedCharacter {
+                    character,
+                    span: Span::new(line, column, 1),
+                })
+            }
+        };
+
+        Ok(Some(Token {
+            kind,
+            span: self.span_from(line, column),
+        }))
+    }
+
+    /// Span from `(line, column)` up to the current position on the same line
+    fn span_from(&self, line: usize, column: usize) -> Span {
+        Span::new(line, column, self.column - column)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Peeks `n` characters past the next one
+    fn lookahead(&self, n: usize) -> Option<char> {
+        self.chars.clone().nth(n)
+    }
+
+    /// The input with every comment blanked out, keeping newlines, quoted
+    /// literals and symbols as they are, to look at the notation before the
+    /// dialect is known
+    pub fn strip_comments(input: &str) -> String {
+        let mut lexer = Lexer::new(input, Dialect::Arrow);
+        let mut text = String::new();
+        while let Some(&c) = lexer.chars.peek() {
+            if c == '#' || (c == '/' && matches!(lexer.lookahead(1), Some('/') | Some('*'))) {
+                let comment = lexer.comment_text();
+                text.extend(comment.chars().map(|c| if c == '\n' { c } else { ' ' }));
+            } else if c == '\'' || c == '"' {
+                text.extend(lexer.bump());
+                while let Some(next) = lexer.bump() {
+                    text.push(next);
+                    if next == '\\' {
+                        text.extend(lexer.bump());
+                    } else if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            } else if Self::is_symbol_start(c) {
+                // A prime ends a symbol such as `E'` instead of opening a literal
+                text.push_str(&lexer.symbol());
+            } else {
+                text.extend(lexer.bump());
+            }
+        }
+        text
+    }
+
+    /// Reads the comment at the current position into a token
+    fn comment(&mut self) -> Token {
+        let (line, column) = (self.line, self.column);
+        let text = self.comment_text();
+        Token {
+            span: Span::new(line, column, text.chars().count()),
+            kind: TokenKind::Comment(text),
+        }
+    }
+
+    /// Reads the comment at the current position, with its markers
+    fn comment_text(&mut self) -> String {
+        let block = self.chars.peek() == Some(&'/') && self.lookahead(1) == Some('*');
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' && !block {
+                break;
+            }
+            text.push(c);
+            self.bump();
+            if block && text.len() >= 4 && text.ends_with("*/") {
+                break;
+            }
+        }
+        text
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// Skips a `/* ... */` comment, which may span lines
+    fn skip_block_comment(&mut self) {
+        self.bump();
+        self.bump();
+        while let Some(c) = self.bump() {
+            if c == '*' && self.chars.peek() == Some(&'/') {
+                self.bump();
+                break;
+            }
+        }
+    }
+
+    fn is_symbol_start(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    /// Arrow-style symbols may carry trailing primes, as in `E'`, and yacc
+    /// symbols may contain dots and dashes, as in `expr.list` or `expr-list`
+    fn is_symbol_char(&self, c: char) -> bool {
+        c.is_alphanumeric()
+            || c == '_'
+            || match self.dialect {
+                Dialect::Arrow | Dialect::Unicode => c == '\'',
+                Dialect::Yacc => c == '.' || c == '-',
+                Dialect::Bnf => false,
+            }
+    }
+
+    fn symbol(&mut self) -> String {
+        let mut symbol = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !self.is_symbol_char(c) {
+                break;
+            }
+            symbol.push(c);
+            self.bump();
+        }
+        symbol
+    }
+
+    /// Reads a `<name>` BNF non-terminal; the name may hold any character but `>`
+    fn bracketed(&mut self, line: usize, column: usize) -> Result<String, GrammarError> {
+        self.bump();
+        let mut name = String::new();
+        loop {
+            match self.chars.peek().copied() {
+                Some('>') if !name.trim().is_empty() => {
+                    self.bump();
+                    return Ok(name.trim().to_string());
+                }
+                Some(c) if c != '>' && c != '\n' => {
+                    self.bump();
+                    name.push(c);
+                }
+                _ => {
+                    return Err(GrammarError::UnexpectedCharacter {
+                        character: '<',
+                        span: Span::new(line, column, 1),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Reads a quoted literal, resolving escape sequences
+    fn literal(&mut self, line: usize, column: usize) -> Result<String, GrammarError> {
+        let quote = self.bump().unwrap();
+        let mut value = String::new();
+
+        loop {
+            match self.chars.peek().copied() {
+                None | Some('\n') => {
+                    return Err(GrammarError::UnterminatedLiteral {
+                        span: self.span_from(line, column),
+                    });
+                }
+                Some(c) if c == quote => {
+                    self.bump();
+                    break;
+                }
+                Some('\\') => value.push(self.escape()?),
+                Some(c) => {
+                    self.bump();
+                    value.push(c);
+                }
+            }
+        }
+
+        let span = self.span_from(line, column);
+        if value.is_empty() {
+            return Err(GrammarError::EmptyLiteral { span });
+        }
+        if value == "ε" || value == "$" {
+            return Err(GrammarError::ReservedLiteral {
+                literal: value,
+                span,
+            });
+        }
+        Ok(value)
+    }
+
+    /// Reads an escape sequence starting at the backslash
+    fn escape(&mut self) -> Result<char, GrammarError> {
+        let (line, column) = (self.line, self.column);
+        self.bump();
+
+        let escaped = match self.chars.peek().copied() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('\'') => '\'',
+            Some('"') => '"',
+            Some('u') => {
+                self.bump();
+                return self.unicode_escape(line, column);
+            }
+            Some(c) if c != '\n' => {
+                self.bump();
+                return Err(GrammarError::InvalidEscape {
+                    sequence: format!("\\{}", c),
+                    span: self.span_from(line, column),
+                });
+            }
+            _ => {
+                return Err(GrammarError::UnterminatedLiteral {
+                    span: self.span_from(line, column),
+                })
+            }
+        };
+        self.bump();
+        Ok(escaped)
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape
+    fn unicode_escape(&mut self, line: usize, column: usize) -> Result<char, GrammarError> {
+        let mut sequence = String::from("\\u");
+        let mut digits = String::new();
+        let mut closed = false;
+
+        if self.chars.peek() == Some(&'{') {
+            sequence.push('{');
+            self.bump();
+            while let Some(&c) = self.chars.peek() {
+                if c == '}' {
+                    sequence.push(c);
+                    self.bump();
+                    closed = true;
+                    break;
+                }
+                if !c.is_ascii_hexdigit() || digits.len() == 6 {
+                    break;
+                }
+                sequence.push(c);
+                digits.push(c);
+                self.bump();
+            }
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| closed)
+            .and_then(char::from_u32)
+            .ok_or_else(|| GrammarError::InvalidEscape {
+                sequence,
+                span: self.span_from(line, column),
+            })
+    }
+}
+
+/// Token display for error messages
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Symbol(symbol) => write!(f, "'{}'", symbol),
+            TokenKind::NonTerminal(name) => write!(f, "'<{}>'", name),
+            TokenKind::Literal(literal) => write!(f, "{}", quote_literal(literal)),
+            TokenKind::Arrow => write!(f, "'->'"),
+            TokenKind::Pipe => write!(f, "'|'"),
+            TokenKind::Newline => write!(f, "end of line"),
+            TokenKind::Directive(name) => write!(f, "'%{}'", name),
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::Star => write!(f, "'*'"),
+            TokenKind::Plus => write!(f, "'+'"),
+            TokenKind::Question => write!(f, "'?'"),
+            TokenKind::Semicolon => write!(f, "';'"),
+            TokenKind::Comment(_) => write!(f, "comment"),
+        }
+    }
+}
+
+/// Renders a terminal so that the lexer reads it back as the same symbol,
+/// quoting it when it is not a plain lowercase word
+pub fn quote_terminal(terminal: &str) -> String {
+    if terminal.chars().all(|c| c.is_lowercase()) {
+        terminal.to_string()
+    } else {
+        quote_literal(terminal)
+    }
+}
+
+/// Renders a literal in single quotes, escaping what needs it
+pub fn quote_literal(literal: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in literal.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
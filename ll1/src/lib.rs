@@ -0,0 +1,21 @@
+pub mod builder;
+pub mod cli;
+pub mod counterexample;
+pub mod dialect;
+pub mod error;
+pub mod first_follow;
+pub mod format;
+pub mod grammar;
+pub mod import;
+pub mod lexer;
+pub mod parser;
+pub mod print;
+pub mod reader;
+pub mod table;
+pub mod transform;
+pub mod tree;
+pub mod validation;
+
+pub use grammar::{Grammar, Production};
+pub use parser::Parser;
+pub use table::ParsingTable;
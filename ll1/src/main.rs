@@ -1,20 +1,37 @@
-pub mod first_follow;
-pub mod grammar;
-pub mod parser;
-pub mod print;
-pub mod table;
-pub mod validation;
-
 use std::error::Error;
 
-use grammar::{Grammar, Production};
-use parser::Parser;
-use table::ParsingTable;
+use ll1::cli::Command;
+use ll1::{Grammar, Parser};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match Command::parse(&args) {
+        Ok(Some(command)) => {
+            if let Err(e) = command.run() {
+                eprintln!("❌ Error: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    }
+
     // let grammar = Grammar::from_file("src/input.txt")?;
     let grammar = Grammar::from_string("A -> B", "A")?;
 
+    let diagnostics = grammar.validate();
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+        println!("❌ The grammar has errors, analysis skipped");
+        return Ok(());
+    }
+
     grammar.print_input_grammar();
     grammar.print_first_set();
     grammar.print_follow_set();
@@ -25,7 +42,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let mut parser = match Parser::new(grammar) {
             Ok(parser) => parser,
             Err(e) => {
-                return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, e)));
+                return Err(Box::new(std::io::Error::other(e)));
             }
         };
 
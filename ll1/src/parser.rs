@@ -1,12 +1,19 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::VecDeque;
 use std::io::{self, Write};
 
+use indexmap::IndexSet;
+
+use super::first_follow::SymbolSets;
+use super::grammar::Production;
+use super::tree::ParseTree;
 use super::{Grammar, ParsingTable};
 pub struct Parser {
     grammar: Grammar,
     parsing_table: ParsingTable,
-    input: Vec<char>,
-    follow_sets: HashMap<String, HashSet<String>>,
+    input: Vec<String>,
+    follow_sets: SymbolSets,
+    /// Whether `parse_tree` collapses the nodes of unit productions
+    collapse_units: bool,
 }
 
 impl Parser {
@@ -14,24 +21,72 @@ impl Parser {
     pub fn new(grammar: Grammar) -> Result<Self, String> {
         let first_sets = grammar.compute_first_sets();
         let follow_sets = grammar.compute_follow_sets(&first_sets);
-        let parsing_table = ParsingTable::build(&grammar)?;
+        let parsing_table = ParsingTable::build(&grammar).map_err(|report| report.to_string())?;
 
         Ok(Parser {
             grammar,
             parsing_table,
             input: Vec::new(),
             follow_sets,
+            collapse_units: false,
         })
     }
 
+    /// Keeps the unit productions of the grammar but leaves their nodes out
+    /// of the trees built by `parse_tree`, as `ParseTree::collapse_units` does
+    pub fn set_collapse_units(&mut self, collapse_units: bool) {
+        self.collapse_units = collapse_units;
+    }
+
     /// Set the input string to be parsed
     pub fn set_input(&mut self, input: String) {
-        self.input = input.chars().collect();
+        self.input = self.tokenize(&input);
     }
 
     /// Get the current input as a string
     pub fn get_input(&self) -> String {
-        self.input.iter().collect()
+        self.input.concat()
+    }
+
+    /// Splits the input into terminals, always taking the longest terminal
+    /// that matches. A terminal ending in a letter, digit or `_` only matches
+    /// up to the end of a word; a word no terminal matches that way is split
+    /// into terminals if it can be, as `bbac` into `b b a c`, and otherwise
+    /// becomes a token of its own. Whitespace between terminals is skipped
+    /// and any other unknown character becomes a token of its own.
+    fn tokenize(&self, input: &str) -> Vec<String> {
+        let mut terminals: Vec<&String> = self.grammar.terminals.iter().collect();
+        terminals.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+        let mut tokens = Vec::new();
+        let mut rest = input;
+        while let Some(c) = rest.chars().next() {
+            let matching = terminals.iter().find(|t| {
+                rest.starts_with(t.as_str())
+                    && (!t.ends_with(is_word_char)
+                        || rest[t.len()..]
+                            .chars()
+                            .next()
+                            .is_none_or(|next| !is_word_char(next)))
+            });
+            if let Some(terminal) = matching {
+                tokens.push(terminal.to_string());
+                rest = &rest[terminal.len()..];
+            } else if is_word_char(c) {
+                let len = rest.find(|next| !is_word_char(next)).unwrap_or(rest.len());
+                match split_word(&rest[..len], &terminals) {
+                    Some(split) => tokens.extend(split),
+                    None => tokens.push(rest[..len].to_string()),
+                }
+                rest = &rest[len..];
+            } else {
+                if !c.is_whitespace() {
+                    tokens.push(c.to_string());
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        tokens
     }
 
     /// Takes user input via stdin
@@ -54,7 +109,7 @@ impl Parser {
         }
 
         let top = stack.back().unwrap();
-        let current_input = self.input[input_pos].to_string();
+        let current_input = self.input[input_pos].clone();
 
         // Check if top terminal matches current input
         if self.grammar.terminals.contains(top) {
@@ -122,12 +177,12 @@ impl Parser {
                     let sync_tokens = if let Some(top) = stack.back() {
                         self.follow_sets.get(top).cloned().unwrap_or_default()
                     } else {
-                        HashSet::new()
+                        IndexSet::new()
                     };
 
                     let mut temp_pos = *input_pos;
                     while temp_pos < self.input.len() {
-                        let current = self.input[temp_pos].to_string();
+                        let current = self.input[temp_pos].clone();
                         if sync_tokens.contains(&current) {
                             stack.pop_back();
                             *input_pos = temp_pos;
@@ -167,8 +222,8 @@ impl Parser {
         stack.push_back("$".to_string());
         stack.push_back(self.grammar.start_symbol.clone());
 
-        if self.input.last() != Some(&'$') {
-            self.input.push('$');
+        if self.input.last().map(String::as_str) != Some("$") {
+            self.input.push("$".to_string());
         }
 
         let mut input_pos = 0;
@@ -180,7 +235,7 @@ impl Parser {
                 return Err("Too many errors encountered. Aborting parse.".to_string());
             }
 
-            let current_input = self.input[input_pos].to_string();
+            let current_input = self.input[input_pos].clone();
             self.print_state(&stack, input_pos);
 
             let top = stack.pop_back().ok_or("Stack unexpectedly empty")?;
@@ -238,7 +293,7 @@ impl Parser {
         // The parse is successful if we've consumed all meaningful input
         // (except possibly $) and the stack is either empty or only contains the end marker
         if (input_pos == self.input.len()
-            || (input_pos == self.input.len() - 1 && self.input[input_pos] == '$'))
+            || (input_pos == self.input.len() - 1 && self.input[input_pos] == "$"))
             && (stack.is_empty() || (stack.len() == 1 && stack.back() == Some(&"$".to_string())))
         {
             if error_count > 0 {
@@ -250,13 +305,140 @@ impl Parser {
         }
     }
 
+    /// Parses the input into a parse tree, stopping at the first error
+    /// instead of recovering from it
+    pub fn parse_tree(&self) -> Result<ParseTree, String> {
+        let end = match self.input.last() {
+            Some(last) if last == "$" => self.input.len() - 1,
+            _ => self.input.len(),
+        };
+        let mut input_pos = 0;
+        let tree = self.subtree(
+            &self.grammar.start_symbol,
+            &self.input[..end],
+            &mut input_pos,
+        )?;
+        if input_pos < end {
+            return Err(format!(
+                "Parsing failed: extra input starting at {}",
+                self.input[input_pos]
+            ));
+        }
+
+        if self.collapse_units {
+            Ok(tree.collapse_units())
+        } else {
+            Ok(tree)
+        }
+    }
+
+    /// Parses the tree of `symbol` from `input` at `input_pos`
+    fn subtree(
+        &self,
+        symbol: &str,
+        input: &[String],
+        input_pos: &mut usize,
+    ) -> Result<ParseTree, String> {
+        let current = input.get(*input_pos).map_or("$", String::as_str);
+
+        if self.grammar.terminals.contains(symbol) {
+            if symbol != current {
+                return Err(format!(
+                    "Terminal mismatch: expected {}, found {}",
+                    symbol, current
+                ));
+            }
+            *input_pos += 1;
+            return Ok(ParseTree::Leaf(symbol.to_string()));
+        }
+
+        let derivation = self
+            .parsing_table
+            .table
+            .get(&(symbol.to_string(), current.to_string()))
+            .ok_or_else(|| format!("No production found for ({}, {})", symbol, current))?;
+        let children = derivation
+            .iter()
+            .filter(|child| *child != "ε")
+            .map(|child| self.subtree(child, input, input_pos))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ParseTree::Node {
+            production: Production {
+                non_terminal: symbol.to_string(),
+                derivation: derivation.clone(),
+                span: None,
+            },
+            children,
+        })
+    }
+
     /// Helper method to print the current parsing state
     fn print_state(&self, stack: &VecDeque<String>, input_pos: usize) {
         println!("Stack: {:?}", stack);
-        println!(
-            "Input remaining: {}",
-            self.input.iter().skip(input_pos).collect::<String>()
-        );
+        println!("Input remaining: {}", self.input[input_pos..].join(" "));
         println!("---");
     }
 }
+
+/// Whether `c` can be part of a word such as `if` or `id`
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Splits `word` into `terminals`, preferring them in the given order, or
+/// `None` when it is not made of terminals only
+fn split_word(word: &str, terminals: &[&String]) -> Option<Vec<String>> {
+    // The terminal to take at each char boundary for the rest to split,
+    // worked out from the end of the word
+    let mut take: Vec<Option<&String>> = vec![None; word.len() + 1];
+    let mut splits = vec![false; word.len() + 1];
+    splits[word.len()] = true;
+    for (i, _) in word.char_indices().rev() {
+        take[i] = terminals
+            .iter()
+            .find(|t| !t.is_empty() && word[i..].starts_with(t.as_str()) && splits[i + t.len()])
+            .copied();
+        splits[i] = take[i].is_some();
+    }
+
+    let mut split = Vec::new();
+    let mut i = 0;
+    while i < word.len() {
+        let terminal = take[i]?;
+        split.push(terminal.clone());
+        i += terminal.len();
+    }
+    Some(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::Grammar;
+
+    fn tokens(grammar: &str, input: &str) -> Vec<String> {
+        let grammar = Grammar::from_string(grammar, "S").unwrap();
+        Parser::new(grammar).unwrap().tokenize(input)
+    }
+
+    #[test]
+    fn terminals_match_whole_words() {
+        let grammar = "S -> if x S | x | id C id\nC -> '<=' | '<'\n";
+        assert_eq!(tokens(grammar, "if x x"), ["if", "x", "x"]);
+        assert_eq!(tokens(grammar, "ifz x"), ["ifz", "x"]);
+        assert_eq!(tokens(grammar, "idz"), ["idz"]);
+        assert_eq!(tokens(grammar, "ifx x"), ["if", "x", "x"]);
+        assert_eq!(tokens(grammar, "id<=id"), ["id", "<=", "id"]);
+        assert_eq!(tokens(grammar, "id<id ?"), ["id", "<", "id", "?"]);
+    }
+
+    #[test]
+    fn words_made_of_terminals_are_split() {
+        let grammar = Grammar::from_file("src/input.txt").unwrap();
+        let mut parser = Parser::new(grammar).unwrap();
+        parser.set_input("bbac".to_string());
+        assert_eq!(parser.input, ["b", "b", "a", "c"]);
+        assert!(parser.parse().is_ok());
+    }
+}
@@ -1,27 +1,65 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
+use comfy_table::{presets::UTF8_FULL, Cell, ContentArrangement, Table};
+
+use super::dialect::Dialect;
+use super::format::{layout_rules, Alternative, Rule, DEFAULT_WIDTH};
+use super::grammar::{Production, SymbolOrder};
+use super::lexer::quote_terminal;
+use super::transform::Rewrite;
 use super::{Grammar, ParsingTable};
 
 impl Grammar {
-    /// Method to print the input grammar
+    /// Method to print the input grammar, laid out the way
+    /// `Grammar::format_source` lays out grammar files
     pub fn print_input_grammar(&self) {
         println!("📚 Grammar:");
+        print!("{}", self.layout(&self.productions));
+    }
 
-        let mut productions_by_nt: HashMap<String, Vec<String>> = HashMap::new();
+    /// Prints the rules alone, in notation the grammar reader accepts
+    pub fn print_rules(&self) {
+        print!("{}", self.layout(&self.productions));
+    }
 
-        // Group productions by non-terminal
-        for production in &self.productions {
-            let entry = productions_by_nt
-                .entry(production.non_terminal.clone())
-                .or_insert_with(Vec::new);
-            entry.push(production.derivation.join(" "));
+    /// Lays out `productions` one rule per non-terminal with
+    /// `format::layout_rules`, the EBNF construct behind each helper
+    /// non-terminal as a comment
+    fn layout<'a, I>(&self, productions: I) -> String
+    where
+        I: IntoIterator<Item = &'a Production>,
+    {
+        let mut rules: IndexMap<&String, Rule> = IndexMap::new();
+        for production in productions {
+            rules
+                .entry(&production.non_terminal)
+                .or_insert_with(|| Rule {
+                    name: production.non_terminal.clone(),
+                    alternatives: Vec::new(),
+                })
+                .alternatives
+                .push(Alternative {
+                    text: self.format_derivation(&production.derivation),
+                    ..Alternative::default()
+                });
         }
 
-        // Print productions with pipe for alternatives
-        for (nt, productions) in productions_by_nt {
-            let formatted = productions.join(" | ");
-            println!("{} → {}", nt, formatted);
+        if self.order == SymbolOrder::Sorted {
+            rules.sort_keys();
         }
+
+        let rules = rules
+            .into_values()
+            .map(|mut rule| {
+                if let (Some(origin), Some(last)) =
+                    (self.helpers.get(&rule.name), rule.alternatives.last_mut())
+                {
+                    last.trailing.push(format!("# {}", origin.source));
+                }
+                rule
+            })
+            .collect();
+        layout_rules(rules, Dialect::Unicode, DEFAULT_WIDTH)
     }
 
     /// Method to print the FIRST sets
@@ -31,8 +69,8 @@ impl Grammar {
         // Print FIRST sets without terminals
         println!("\n🔍 FIRST Sets:");
         for (symbol, first_set) in first_sets {
-            // Check if the symbol is a non-terminal (i.e., it starts with an uppercase letter)
-            if symbol.chars().next().unwrap().is_uppercase() {
+            // Only non-terminals are printed, a terminal's FIRST set is itself
+            if self.non_terminals.contains(&symbol) {
                 println!("FIRST({}) = {:?}", symbol, first_set);
             }
         }
@@ -49,6 +87,21 @@ impl Grammar {
         }
     }
 
+    /// Joins a derivation for display, quoting terminals that need it
+    fn format_derivation(&self, derivation: &[String]) -> String {
+        derivation
+            .iter()
+            .map(|symbol| {
+                if self.terminals.contains(symbol) {
+                    quote_terminal(symbol)
+                } else {
+                    symbol.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Print the Parsing Table
     pub fn print_parsing_table(&self) {
         match ParsingTable::build(self) {
@@ -66,3 +119,69 @@ impl Grammar {
         }
     }
 }
+
+impl Rewrite {
+    /// Prints each rule of `original` next to the rules it was rewritten into
+    pub fn print_side_by_side(&self, original: &Grammar) {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![Cell::new("Original"), Cell::new("Rewritten")]);
+
+        // Rewritten productions grouped under the rule of their first origin
+        let mut rewritten: IndexMap<&String, Vec<&Production>> = IndexMap::new();
+        for production in &self.grammar.productions {
+            let from = self
+                .origins
+                .get(production)
+                .and_then(|origins| origins.first())
+                .map_or(&production.non_terminal, |origin| &origin.non_terminal);
+            rewritten.entry(from).or_default().push(production);
+        }
+
+        let mut before: IndexMap<&String, Vec<&Production>> = IndexMap::new();
+        for production in &original.productions {
+            before
+                .entry(&production.non_terminal)
+                .or_default()
+                .push(production);
+        }
+        if original.order == SymbolOrder::Sorted {
+            before.sort_keys();
+        }
+
+        for (non_terminal, productions) in before {
+            let after = rewritten
+                .get(non_terminal)
+                .map(|productions| self.grammar.layout(productions.iter().copied()))
+                .unwrap_or_default();
+            table.add_row(vec![
+                original.layout(productions).trim_end().to_string(),
+                after.trim_end().to_string(),
+            ]);
+        }
+
+        println!("{}", table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::Dialect;
+    use crate::format::DEFAULT_WIDTH;
+    use crate::Grammar;
+
+    #[test]
+    fn rules_are_laid_out_like_formatted_sources() {
+        let source =
+            "STMT → id ':=' E ';' | print '(' ARGS ')' ';' | while E do STMT od | begin STMT end\n\
+                      ARGS → E | ε\n\
+                      E → id X\n\
+                      X → '+' id X | ε\n";
+        let grammar = Grammar::from_string_in(source, "STMT", Dialect::Unicode).unwrap();
+        let formatted = Grammar::format_source(source, Dialect::Unicode, DEFAULT_WIDTH).unwrap();
+        assert_eq!(grammar.layout(&grammar.productions), formatted);
+        assert!(formatted.contains("\n     | print"));
+    }
+}
@@ -0,0 +1,953 @@
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexSet;
+
+use super::dialect::Dialect;
+use super::error::{GrammarError, Span};
+use super::grammar::{EbnfOperator, EbnfOrigin, SymbolMode, SymbolOrder};
+use super::lexer::{quote_literal, Lexer, Token, TokenKind};
+use super::Grammar;
+
+/// An element of an alternative as written in the source
+#[derive(Debug)]
+enum Item<'a> {
+    /// A bare symbol, a BNF non-terminal or a quoted literal
+    Symbol(&'a Token),
+    /// A parenthesised group or a symbol followed by an EBNF operator
+    Group {
+        alternatives: Vec<Vec<Item<'a>>>,
+        operator: EbnfOperator,
+    },
+}
+
+/// A non-terminal token and the items of each of its alternatives
+type Rule<'a> = (&'a Token, Vec<Vec<Item<'a>>>);
+
+/// Settings collected from `%` directives, which may appear on any line
+#[derive(Debug, Default)]
+struct Directives {
+    dialect: Dialect,
+    start: Option<(String, Span)>,
+    tokens: IndexSet<String>,
+    epsilons: HashSet<String>,
+    name: Option<String>,
+    symbol_mode: SymbolMode,
+    order: SymbolOrder,
+}
+
+/// Turns parsed rules into productions, lowering EBNF constructs into
+/// right-recursive helper non-terminals
+struct Lowering<'a> {
+    grammar: Grammar,
+    directives: &'a Directives,
+    /// Every name written in the source, so that helpers never shadow one
+    reserved: HashSet<String>,
+    /// Helpers created so far, keyed by operator and lowered alternatives
+    helpers: HashMap<(EbnfOperator, Vec<Vec<String>>), String>,
+    /// Helper productions waiting for their parent rule to be added first
+    pending: Vec<(String, Vec<String>)>,
+    /// Literals and bare non-terminals share one namespace once quotes are stripped
+    literals: Vec<&'a Token>,
+    bare_non_terminals: HashSet<&'a str>,
+    /// Names with rules, which are the non-terminals unless kinds come from
+    /// letter case or BNF brackets
+    defined: HashSet<&'a str>,
+}
+
+impl Grammar {
+    /// Reads grammar source text made of rules, comments and directives.
+    ///
+    /// The start symbol is `start_symbol` when given, otherwise the one named by
+    /// `%start` (or a legacy first line holding just the symbol), otherwise the
+    /// left-hand side of the first rule.
+    pub(crate) fn read(
+        input: &str,
+        start_symbol: Option<&str>,
+        dialect: Dialect,
+    ) -> Result<Self, GrammarError> {
+        let tokens = Lexer::tokenize(input, dialect)?;
+        let mut directives = Directives {
+            dialect,
+            ..Directives::default()
+        };
+        let mut rules: Vec<Rule> = Vec::new();
+        let mut pos = 0;
+
+        while let Some(token) = tokens.get(pos) {
+            match &token.kind {
+                TokenKind::Newline => pos += 1,
+                TokenKind::Directive(name) => {
+                    let end = Self::line_end(&tokens, pos);
+                    directives.apply(name, token, &tokens[pos + 1..end])?;
+                    pos = end;
+                }
+                _ if Self::is_rule_head(&tokens, pos) => {
+                    let end = Self::rule_end(&tokens, pos);
+                    rules.push(Self::parse_rule(&tokens[pos..end])?);
+                    pos = end;
+                }
+                _ => {
+                    let end = Self::line_end(&tokens, pos);
+                    match &tokens[pos..end] {
+                        // Legacy header: a first line holding only the start symbol
+                        [symbol] if rules.is_empty() => directives.set_start(symbol)?,
+                        _ if !matches!(
+                            token.kind,
+                            TokenKind::Symbol(_) | TokenKind::NonTerminal(_)
+                        ) =>
+                        {
+                            return Err(Self::unexpected_token(token))
+                        }
+                        _ => return Err(GrammarError::ExpectedArrow { span: token.span }),
+                    }
+                    pos = end;
+                }
+            }
+        }
+
+        let (start_symbol, start_span) = match (start_symbol, &directives.start) {
+            (Some(given), Some((declared, span))) if given != declared => {
+                return Err(GrammarError::InvalidStartSymbol {
+                    symbol: given.to_string(),
+                    reason: format!("conflicts with %start '{}'", declared),
+                    span: Some(*span),
+                })
+            }
+            (Some(given), _) => (given.to_string(), None),
+            (None, Some((declared, span))) => (declared.clone(), Some(*span)),
+            (None, None) => match rules.first() {
+                Some((non_terminal, _)) => (
+                    directives.non_terminal(non_terminal)?.to_string(),
+                    Some(non_terminal.span),
+                ),
+                None => return Err(GrammarError::EmptyGrammar),
+            },
+        };
+        if directives.by_case() {
+            Self::validate_start_symbol(&start_symbol, start_span)?;
+        }
+        if directives.tokens.contains(&start_symbol) {
+            return Err(GrammarError::InvalidStartSymbol {
+                symbol: start_symbol,
+                reason: "is declared as a %token".to_string(),
+                span: start_span,
+            });
+        }
+
+        let mut grammar = Grammar::new(&start_symbol);
+        grammar.name = directives.name.clone();
+        grammar.dialect = dialect;
+        grammar.symbol_mode = directives.symbol_mode;
+        grammar.order = directives.order;
+        for token in &directives.tokens {
+            grammar.add_terminal(token);
+        }
+        // Non-terminals are listed in the order their rules appear
+        for (non_terminal, _) in &rules {
+            grammar
+                .non_terminals
+                .insert(directives.non_terminal(non_terminal)?.to_string());
+        }
+
+        let reserved = tokens
+            .iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Symbol(symbol)
+                | TokenKind::NonTerminal(symbol)
+                | TokenKind::Literal(symbol) => Some(symbol.clone()),
+                _ => None,
+            })
+            .collect();
+        let defined = rules
+            .iter()
+            .map(|(non_terminal, _)| directives.non_terminal(non_terminal))
+            .collect::<Result<_, _>>()?;
+        let mut lowering = Lowering {
+            grammar,
+            directives: &directives,
+            reserved,
+            helpers: HashMap::new(),
+            pending: Vec::new(),
+            literals: Vec::new(),
+            bare_non_terminals: HashSet::new(),
+            defined,
+        };
+
+        for (non_terminal, alternatives) in &rules {
+            let non_terminal_span = non_terminal.span;
+            let non_terminal = directives.non_terminal(non_terminal)?;
+            lowering.bare_non_terminals.insert(non_terminal);
+
+            for alternative in alternatives {
+                let mut derivation = lowering.sequence(non_terminal, alternative)?;
+                // An empty alternative, as in yacc's `opt : | x ;`, derives ε
+                if derivation.is_empty() {
+                    derivation.push("ε".to_string());
+                }
+                let derivation = derivation.iter().map(String::as_str).collect();
+                let span = first_token(alternative).map_or(non_terminal_span, |token| token.span);
+                lowering
+                    .grammar
+                    .add_production_at(non_terminal, derivation, Some(span));
+            }
+            lowering.flush();
+        }
+
+        lowering.finish()
+    }
+
+    /// Index of the newline ending the line that contains `pos`, or the end of the tokens
+    pub(crate) fn line_end(tokens: &[Token], pos: usize) -> usize {
+        tokens[pos..]
+            .iter()
+            .position(|token| token.kind == TokenKind::Newline)
+            .map_or(tokens.len(), |offset| pos + offset)
+    }
+
+    /// A rule starts with its non-terminal followed by `->`
+    pub(crate) fn is_rule_head(tokens: &[Token], pos: usize) -> bool {
+        Self::rule_arrow(tokens, pos).is_some()
+    }
+
+    /// Index of the `->` of the rule starting at `pos`. The arrow may be on a
+    /// later line than the non-terminal, as is common in yacc files.
+    pub(crate) fn rule_arrow(tokens: &[Token], pos: usize) -> Option<usize> {
+        if !matches!(
+            tokens.get(pos)?.kind,
+            TokenKind::Symbol(_) | TokenKind::NonTerminal(_) | TokenKind::Literal(_)
+        ) {
+            return None;
+        }
+        let arrow = pos
+            + 1
+            + tokens[pos + 1..]
+                .iter()
+                .position(|token| token.kind != TokenKind::Newline)?;
+        (tokens[arrow].kind == TokenKind::Arrow).then_some(arrow)
+    }
+
+    /// Finds where the rule starting at `start` ends, just past its `;` if it has one.
+    ///
+    /// Without a `;` the rule ends with its line, and the following lines only
+    /// continue it when they start with `|` or the previous line ends with one.
+    /// A rule ending with `;` may span any number of lines.
+    pub(crate) fn rule_end(tokens: &[Token], start: usize) -> usize {
+        let arrow = Self::rule_arrow(tokens, start).unwrap_or(start + 1);
+
+        // The rule cannot extend past the next line starting a rule or a directive
+        let mut limit = tokens.len();
+        for pos in arrow + 1..tokens.len() {
+            match &tokens[pos].kind {
+                TokenKind::Semicolon => return pos + 1,
+                TokenKind::Newline
+                    if Self::is_rule_head(tokens, pos + 1)
+                        || matches!(
+                            tokens.get(pos + 1).map(|token| &token.kind),
+                            Some(TokenKind::Directive(_))
+                        ) =>
+                {
+                    limit = pos;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let mut previous = &tokens[arrow];
+        for pos in arrow + 1..limit {
+            let token = &tokens[pos];
+            if token.kind == TokenKind::Newline {
+                continue;
+            }
+            if tokens[pos - 1].kind == TokenKind::Newline
+                && token.kind != TokenKind::Pipe
+                && !matches!(previous.kind, TokenKind::Pipe | TokenKind::Arrow)
+            {
+                return pos - 1;
+            }
+            previous = token;
+        }
+        limit
+    }
+
+    /// Splits the tokens of a rule into its non-terminal and the items of each alternative
+    fn parse_rule(tokens: &[Token]) -> Result<Rule<'_>, GrammarError> {
+        let arrow = Self::rule_arrow(tokens, 0).unwrap_or(1);
+        let body: Vec<&Token> = tokens[arrow + 1..]
+            .iter()
+            .filter(|token| !matches!(token.kind, TokenKind::Newline | TokenKind::Semicolon))
+            .collect();
+        let mut pos = 0;
+        let alternatives = Self::parse_alternatives(&body, &mut pos)?;
+        if let Some(token) = body.get(pos) {
+            return Err(Self::unexpected_token(token));
+        }
+        Ok((&tokens[0], alternatives))
+    }
+
+    /// Parses `|`-separated alternatives up to a closing `)` or the end of the tokens
+    fn parse_alternatives<'a>(
+        tokens: &[&'a Token],
+        pos: &mut usize,
+    ) -> Result<Vec<Vec<Item<'a>>>, GrammarError> {
+        let mut alternatives = vec![Self::parse_sequence(tokens, pos)?];
+        while tokens.get(*pos).map(|token| &token.kind) == Some(&TokenKind::Pipe) {
+            *pos += 1;
+            alternatives.push(Self::parse_sequence(tokens, pos)?);
+        }
+        Ok(alternatives)
+    }
+
+    /// Parses a sequence of symbols and groups, each with an optional EBNF operator
+    fn parse_sequence<'a>(
+        tokens: &[&'a Token],
+        pos: &mut usize,
+    ) -> Result<Vec<Item<'a>>, GrammarError> {
+        let mut items = Vec::new();
+
+        while let Some(&token) = tokens.get(*pos) {
+            let item = match &token.kind {
+                TokenKind::Symbol(_) | TokenKind::NonTerminal(_) | TokenKind::Literal(_) => {
+                    *pos += 1;
+                    Item::Symbol(token)
+                }
+                TokenKind::LParen => {
+                    *pos += 1;
+                    let alternatives = Self::parse_alternatives(tokens, pos)?;
+                    match tokens.get(*pos).map(|token| &token.kind) {
+                        Some(TokenKind::RParen) => *pos += 1,
+                        _ => return Err(GrammarError::UnclosedGroup { span: token.span }),
+                    }
+                    Item::Group {
+                        alternatives,
+                        operator: EbnfOperator::Group,
+                    }
+                }
+                _ => break,
+            };
+
+            let operator = match tokens.get(*pos).map(|token| &token.kind) {
+                Some(TokenKind::Star) => Some(EbnfOperator::Star),
+                Some(TokenKind::Plus) => Some(EbnfOperator::Plus),
+                Some(TokenKind::Question) => Some(EbnfOperator::Optional),
+                _ => None,
+            };
+
+            let item = match operator {
+                None => item,
+                Some(operator) => {
+                    *pos += 1;
+                    if let Some(&next) = tokens.get(*pos) {
+                        if matches!(
+                            next.kind,
+                            TokenKind::Star | TokenKind::Plus | TokenKind::Question
+                        ) {
+                            return Err(GrammarError::StackedOperator { span: next.span });
+                        }
+                    }
+                    match item {
+                        Item::Group {
+                            alternatives,
+                            operator: EbnfOperator::Group,
+                        } => Item::Group {
+                            alternatives,
+                            operator,
+                        },
+                        item => Item::Group {
+                            alternatives: vec![vec![item]],
+                            operator,
+                        },
+                    }
+                }
+            };
+            items.push(item);
+        }
+
+        Ok(items)
+    }
+
+    fn unexpected_token(token: &Token) -> GrammarError {
+        GrammarError::UnexpectedToken {
+            found: token.kind.to_string(),
+            span: token.span,
+        }
+    }
+}
+
+impl<'a> Lowering<'a> {
+    /// Lowers a sequence of items into the symbols of one derivation
+    fn sequence(
+        &mut self,
+        non_terminal: &str,
+        items: &'a [Item<'a>],
+    ) -> Result<Vec<String>, GrammarError> {
+        let mut derivation = Vec::new();
+
+        for item in items {
+            match item {
+                Item::Symbol(token) => derivation.push(self.symbol(token)?),
+                Item::Group {
+                    alternatives,
+                    operator,
+                } => {
+                    let mut lowered = Vec::new();
+                    for alternative in alternatives {
+                        let mut symbols = self.sequence(non_terminal, alternative)?;
+                        if symbols.is_empty() {
+                            symbols.push("ε".to_string());
+                        }
+                        lowered.push(symbols);
+                    }
+
+                    if *operator == EbnfOperator::Group && lowered.len() == 1 {
+                        // A plain group with a single alternative is just its contents
+                        let symbols = lowered.pop().unwrap();
+                        derivation.extend(symbols.into_iter().filter(|s| s != "ε"));
+                    } else {
+                        let source =
+                            format!("{}{}", render_operand(alternatives), operator.suffix());
+                        derivation.push(self.helper(non_terminal, *operator, lowered, source));
+                    }
+                }
+            }
+        }
+
+        Ok(derivation)
+    }
+
+    /// Classifies a bare symbol or literal and registers terminals.
+    ///
+    /// Every symbol's kind is registered here, from the BNF brackets or from
+    /// whether it has rules, except in `%symbols case` mode where bare symbols
+    /// are left to the case heuristic.
+    fn symbol(&mut self, token: &'a Token) -> Result<String, GrammarError> {
+        let directives = self.directives;
+        match &token.kind {
+            TokenKind::Literal(literal) => {
+                self.grammar.add_terminal(literal);
+                self.literals.push(token);
+                Ok(literal.clone())
+            }
+            TokenKind::NonTerminal(name) => {
+                self.grammar.non_terminals.insert(name.clone());
+                self.bare_non_terminals.insert(name);
+                Ok(name.clone())
+            }
+            TokenKind::Symbol(symbol) if directives.is_epsilon(symbol) => Ok("ε".to_string()),
+            TokenKind::Symbol(symbol) if directives.tokens.contains(symbol) => {
+                self.grammar.add_terminal(symbol);
+                Ok(symbol.clone())
+            }
+            TokenKind::Symbol(symbol)
+                if directives.dialect != Dialect::Bnf && self.defined.contains(symbol.as_str()) =>
+            {
+                self.grammar.non_terminals.insert(symbol.clone());
+                Ok(symbol.clone())
+            }
+            TokenKind::Symbol(symbol) if !directives.by_case() => {
+                self.grammar.add_terminal(symbol);
+                self.literals.push(token);
+                Ok(symbol.clone())
+            }
+            TokenKind::Symbol(symbol) if Grammar::is_valid_symbol(symbol) => {
+                if Grammar::is_non_terminal(symbol) {
+                    self.bare_non_terminals.insert(symbol);
+                }
+                Ok(symbol.clone())
+            }
+            TokenKind::Symbol(symbol) => Err(GrammarError::InvalidSymbol {
+                symbol: symbol.clone(),
+                span: token.span,
+            }),
+            _ => Err(Grammar::unexpected_token(token)),
+        }
+    }
+
+    /// Returns the helper non-terminal for an EBNF construct, creating it on first use:
+    ///
+    /// - `(α | β)` becomes `H -> α | β`
+    /// - `(α)?` becomes `H -> α | ε`
+    /// - `(α)*` becomes `H -> α H | ε`
+    /// - `(α)+` becomes `H -> α T` where `T` is the helper for `(α)*`
+    fn helper(
+        &mut self,
+        non_terminal: &str,
+        operator: EbnfOperator,
+        alternatives: Vec<Vec<String>>,
+        source: String,
+    ) -> String {
+        let key = (operator, alternatives);
+        if let Some(helper) = self.helpers.get(&key) {
+            return helper.clone();
+        }
+        let (operator, alternatives) = key;
+
+        let name = self.fresh_name(non_terminal);
+        self.grammar.non_terminals.insert(name.clone());
+        // Helpers created for this one, such as the tail of `X+`, come after it
+        let position = self.pending.len();
+        self.helpers
+            .insert((operator, alternatives.clone()), name.clone());
+        self.grammar.helpers.insert(
+            name.clone(),
+            EbnfOrigin {
+                operator,
+                source: source.clone(),
+            },
+        );
+
+        let is_epsilon =
+            |alternative: &Vec<String>| alternative.len() == 1 && alternative[0] == "ε";
+        let mut derivations: Vec<Vec<String>> = Vec::new();
+        match operator {
+            EbnfOperator::Group => derivations = alternatives,
+            EbnfOperator::Optional => {
+                derivations.extend(alternatives.into_iter().filter(|alt| !is_epsilon(alt)));
+                derivations.push(vec!["ε".to_string()]);
+            }
+            EbnfOperator::Star => {
+                for alternative in alternatives.into_iter().filter(|alt| !is_epsilon(alt)) {
+                    derivations.push([alternative, vec![name.clone()]].concat());
+                }
+                derivations.push(vec!["ε".to_string()]);
+            }
+            EbnfOperator::Plus => {
+                let star_source = format!("{}*", source.strip_suffix('+').unwrap_or(&source));
+                let tail = self.helper(
+                    non_terminal,
+                    EbnfOperator::Star,
+                    alternatives.clone(),
+                    star_source,
+                );
+                for alternative in alternatives {
+                    let head = if is_epsilon(&alternative) {
+                        Vec::new()
+                    } else {
+                        alternative
+                    };
+                    derivations.push([head, vec![tail.clone()]].concat());
+                }
+            }
+        }
+
+        let productions = derivations
+            .into_iter()
+            .map(|derivation| (name.clone(), derivation));
+        self.pending.splice(position..position, productions);
+        name
+    }
+
+    /// Picks the first unused helper name: `EA`, `EB`, ... after an
+    /// uppercase rule name, so that the printed grammar reads back the same
+    /// under `%symbols case`, and `expr_1`, `expr_2`, ... after any other
+    fn fresh_name(&mut self, non_terminal: &str) -> String {
+        let uppercase = Grammar::is_non_terminal(non_terminal);
+        let mut n = 1;
+        loop {
+            let name = if uppercase {
+                format!("{}{}", non_terminal, letters(n))
+            } else {
+                format!("{}_{}", non_terminal, n)
+            };
+            if !self.reserved.contains(&name) && !self.grammar.non_terminals.contains(&name) {
+                self.reserved.insert(name.clone());
+                return name;
+            }
+            n += 1;
+        }
+    }
+
+    /// Adds the helper productions created while lowering the last rule
+    fn flush(&mut self) {
+        for (helper, derivation) in std::mem::take(&mut self.pending) {
+            let derivation = derivation.iter().map(String::as_str).collect();
+            self.grammar.add_production(&helper, derivation);
+        }
+    }
+
+    fn finish(self) -> Result<Grammar, GrammarError> {
+        for token in self.literals {
+            if let TokenKind::Literal(literal) | TokenKind::Symbol(literal) = &token.kind {
+                if self.bare_non_terminals.contains(literal.as_str()) {
+                    return Err(GrammarError::SymbolClash {
+                        symbol: literal.clone(),
+                        span: token.span,
+                    });
+                }
+            }
+        }
+        Ok(self.grammar)
+    }
+}
+
+/// `A`, `B`, ..., `Z`, `AA`, `AB`, ... for 1, 2, ...
+fn letters(mut n: usize) -> String {
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push(char::from(b'A' + (n % 26) as u8));
+        n /= 26;
+    }
+    letters.into_iter().rev().collect()
+}
+
+/// The first token written in a sequence of items
+fn first_token<'a>(items: &[Item<'a>]) -> Option<&'a Token> {
+    match items.first()? {
+        Item::Symbol(token) => Some(token),
+        Item::Group { alternatives, .. } => first_token(alternatives.first()?),
+    }
+}
+
+/// Renders the operand of an EBNF operator the way it was written
+fn render_operand(alternatives: &[Vec<Item>]) -> String {
+    match alternatives {
+        [alternative] if alternative.len() == 1 => render_item(&alternative[0]),
+        _ => format!("({})", render_alternatives(alternatives)),
+    }
+}
+
+fn render_alternatives(alternatives: &[Vec<Item>]) -> String {
+    alternatives
+        .iter()
+        .map(|alternative| {
+            alternative
+                .iter()
+                .map(render_item)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn render_item(item: &Item) -> String {
+    match item {
+        Item::Symbol(Token {
+            kind: TokenKind::Literal(literal),
+            ..
+        }) => quote_literal(literal),
+        Item::Symbol(Token {
+            kind: TokenKind::Symbol(symbol),
+            ..
+        }) => symbol.clone(),
+        Item::Symbol(Token {
+            kind: TokenKind::NonTerminal(name),
+            ..
+        }) => format!("<{}>", name),
+        Item::Symbol(_) => String::new(),
+        Item::Group {
+            alternatives,
+            operator,
+        } => format!("{}{}", render_operand(alternatives), operator.suffix()),
+    }
+}
+
+impl Directives {
+    /// Records a directive given its name token and its arguments
+    fn apply(&mut self, name: &str, directive: &Token, args: &[Token]) -> Result<(), GrammarError> {
+        let invalid = |reason: &str| GrammarError::InvalidDirective {
+            name: name.to_string(),
+            reason: reason.to_string(),
+            span: directive.span,
+        };
+        match name {
+            "start" => match args {
+                [symbol] => self.set_start(symbol),
+                _ => Err(invalid("expects a single symbol")),
+            },
+            "token" if !args.is_empty() => {
+                for arg in args {
+                    match &arg.kind {
+                        TokenKind::Symbol(symbol) | TokenKind::Literal(symbol) => {
+                            self.tokens.insert(symbol.clone());
+                        }
+                        _ => return Err(Grammar::unexpected_token(arg)),
+                    }
+                }
+                Ok(())
+            }
+            "epsilon" if !args.is_empty() => {
+                for arg in args {
+                    match &arg.kind {
+                        TokenKind::Symbol(symbol) => {
+                            self.epsilons.insert(symbol.clone());
+                        }
+                        _ => return Err(Grammar::unexpected_token(arg)),
+                    }
+                }
+                Ok(())
+            }
+            "name" => match args {
+                [Token {
+                    kind: TokenKind::Symbol(value) | TokenKind::Literal(value),
+                    ..
+                }] => {
+                    self.name = Some(value.clone());
+                    Ok(())
+                }
+                _ => Err(invalid("expects a single name")),
+            },
+            "symbols" => match args {
+                [Token {
+                    kind: TokenKind::Symbol(mode),
+                    ..
+                }] if mode == "declared" || mode == "case" => {
+                    self.symbol_mode = if mode == "case" {
+                        SymbolMode::Case
+                    } else {
+                        SymbolMode::Declared
+                    };
+                    Ok(())
+                }
+                _ => Err(invalid("expects 'declared' or 'case'")),
+            },
+            "order" => match args {
+                [Token {
+                    kind: TokenKind::Symbol(order),
+                    ..
+                }] if order == "source" || order == "sorted" => {
+                    self.order = if order == "sorted" {
+                        SymbolOrder::Sorted
+                    } else {
+                        SymbolOrder::Source
+                    };
+                    Ok(())
+                }
+                _ => Err(invalid("expects 'source' or 'sorted'")),
+            },
+            "token" | "epsilon" => Err(invalid("expects at least one symbol")),
+            _ => Err(GrammarError::UnknownDirective {
+                name: name.to_string(),
+                span: directive.span,
+            }),
+        }
+    }
+
+    fn set_start(&mut self, token: &Token) -> Result<(), GrammarError> {
+        let symbol = match &token.kind {
+            TokenKind::Symbol(symbol) | TokenKind::NonTerminal(symbol) => symbol,
+            _ => return Err(Grammar::unexpected_token(token)),
+        };
+        if let Some((previous, _)) = &self.start {
+            return Err(GrammarError::InvalidStartSymbol {
+                symbol: symbol.clone(),
+                reason: format!("is declared after start symbol '{}'", previous),
+                span: Some(token.span),
+            });
+        }
+        self.start = Some((symbol.clone(), token.span));
+        Ok(())
+    }
+
+    /// Whether bare symbols get their kind from letter case
+    fn by_case(&self) -> bool {
+        self.symbol_mode == SymbolMode::Case && !self.dialect.kinds_by_syntax()
+    }
+
+    fn is_epsilon(&self, symbol: &str) -> bool {
+        symbol == "ε" || self.epsilons.contains(symbol)
+    }
+
+    /// Checks the left-hand side of a rule
+    fn non_terminal<'a>(&self, token: &'a Token) -> Result<&'a str, GrammarError> {
+        let symbol = match &token.kind {
+            TokenKind::Symbol(symbol) | TokenKind::NonTerminal(symbol) => symbol.as_str(),
+            TokenKind::Literal(literal) => {
+                return Err(GrammarError::InvalidNonTerminal {
+                    symbol: literal.clone(),
+                    reason: "cannot be a quoted literal".to_string(),
+                    span: token.span,
+                })
+            }
+            _ => return Err(Grammar::unexpected_token(token)),
+        };
+        if self.tokens.contains(symbol) {
+            return Err(GrammarError::InvalidNonTerminal {
+                symbol: symbol.to_string(),
+                reason: "is declared as a %token and cannot have productions".to_string(),
+                span: token.span,
+            });
+        }
+        if self.by_case() {
+            Grammar::validate_non_terminal(symbol, token.span)?;
+        }
+        Ok(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::Dialect;
+    use crate::grammar::SymbolMode;
+    use crate::lexer::quote_terminal;
+    use crate::Grammar;
+
+    const SOURCE: &str = "%symbols case\nS -> A+ end\nA -> x (y | z)?\n";
+
+    /// The productions in arrow notation, one per line
+    fn render(grammar: &Grammar) -> String {
+        let mut text = String::from("%symbols case\n");
+        for production in &grammar.productions {
+            let derivation: Vec<String> = production
+                .derivation
+                .iter()
+                .map(|symbol| match grammar.terminals.contains(symbol) {
+                    true => quote_terminal(symbol),
+                    false => symbol.clone(),
+                })
+                .collect();
+            text.push_str(&format!(
+                "{} -> {}\n",
+                production.non_terminal,
+                derivation.join(" ")
+            ));
+        }
+        text
+    }
+
+    #[test]
+    fn helpers_come_in_the_order_they_were_created() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let helpers: Vec<&String> = grammar.helpers.keys().collect();
+        assert_eq!(helpers, ["SA", "SB", "AA"]);
+        let mut heads: Vec<&String> = grammar
+            .productions
+            .iter()
+            .map(|p| &p.non_terminal)
+            .collect();
+        heads.dedup();
+        assert_eq!(heads, ["S", "SA", "SB", "A", "AA"]);
+    }
+
+    #[test]
+    fn printed_helpers_read_back_in_case_mode() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let text = render(&grammar);
+        let again = Grammar::from_string(&text, "S").unwrap();
+        assert_eq!(again.productions, grammar.productions);
+        assert_eq!(render(&again), text);
+    }
+
+    /// The message and the line, column and length of the error reading `source`
+    fn error(source: &str, dialect: Dialect) -> (String, usize, usize, usize) {
+        let error = Grammar::read(source, None, dialect).unwrap_err();
+        let span = error.span().expect("reader errors have a span");
+        (error.to_string(), span.line, span.column, span.length)
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_text() {
+        for (source, line, column, length) in [
+            ("S -> a @ b\n", 1, 8, 1),
+            ("S -> a\nT -> 'b\n", 2, 6, 2),
+            ("S -> a\n\n%bogus x\n", 3, 1, 6),
+            ("S -> a\n  | (b c\n", 2, 5, 1),
+            ("S -> a\nT b\n", 2, 1, 1),
+            ("%start\nS -> a\n", 1, 1, 6),
+        ] {
+            let (message, found_line, found_column, found_length) = error(source, Dialect::Arrow);
+            assert_eq!(
+                (found_line, found_column, found_length),
+                (line, column, length),
+                "{}",
+                message
+            );
+            assert!(message.starts_with(&format!("Error on line {}, column {}:", line, column)));
+        }
+    }
+
+    /// Each production as `A → α`
+    fn rules(source: &str, dialect: Dialect) -> Vec<String> {
+        Grammar::read(source, None, dialect)
+            .unwrap()
+            .productions
+            .iter()
+            .map(|production| production.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn rules_continue_on_lines_starting_with_a_pipe() {
+        assert_eq!(
+            rules("S -> a\n   | b\n\n   | c\nT -> d |\n  e\n", Dialect::Arrow),
+            ["S → a", "S → b", "S → c", "T → d", "T → e"]
+        );
+        assert_eq!(
+            rules("s\n  : a\n  | b\n  ;\nt : c\n  | d ;\n", Dialect::Yacc),
+            ["s → a", "s → b", "t → c", "t → d"]
+        );
+    }
+
+    #[test]
+    fn rules_end_with_their_semicolon() {
+        assert_eq!(
+            rules("s : a\n  b\n  c ;\nt : d ;\n", Dialect::Yacc),
+            ["s → a b c", "t → d"]
+        );
+    }
+
+    #[test]
+    fn a_rule_left_unterminated_stops_at_its_line() {
+        let (message, line, column, _) = error("s : a\n  b c\n", Dialect::Yacc);
+        assert_eq!((line, column), (2, 3));
+        assert!(
+            message.ends_with("Expected '->' in production"),
+            "{}",
+            message
+        );
+        let (_, line, column, _) = error("S -> a\n  b\nT -> c\n", Dialect::Arrow);
+        assert_eq!((line, column), (2, 3));
+    }
+
+    #[test]
+    fn directives_set_up_the_grammar() {
+        let source = "%name expressions\n%start E\n%token ID\n%epsilon eps\n\
+                      T -> ID\nE -> T X\nX -> '+' T X | eps\n";
+        let grammar = Grammar::read(source, None, Dialect::Arrow).unwrap();
+        assert_eq!(grammar.name.as_deref(), Some("expressions"));
+        assert_eq!(grammar.start_symbol, "E");
+        assert!(grammar.terminals.contains("ID"));
+        assert!(!grammar.non_terminals.contains("ID"));
+        assert!(grammar.productions.iter().any(|p| p.to_string() == "X → ε"));
+        assert!(!grammar.terminals.contains("eps"));
+    }
+
+    #[test]
+    fn symbols_directive_classifies_by_case() {
+        let source = "%symbols case\nS -> a B\nB -> b\n";
+        let grammar = Grammar::read(source, None, Dialect::Arrow).unwrap();
+        assert_eq!(grammar.symbol_mode, SymbolMode::Case);
+        assert_eq!(grammar.terminals.iter().collect::<Vec<_>>(), ["a", "b"]);
+        assert!(grammar.non_terminals.contains("B"));
+    }
+
+    #[test]
+    fn reports_unknown_and_malformed_directives() {
+        let (message, ..) = error("%tokens ID\nS -> ID\n", Dialect::Arrow);
+        assert!(
+            message.contains("Unknown directive '%tokens'"),
+            "{}",
+            message
+        );
+        for (source, expected) in [
+            ("%start S T\nS -> a\n", "%start expects a single symbol"),
+            ("%token\nS -> a\n", "%token expects at least one symbol"),
+            (
+                "%symbols mixed\nS -> a\n",
+                "%symbols expects 'declared' or 'case'",
+            ),
+        ] {
+            let (message, ..) = error(source, Dialect::Arrow);
+            assert!(message.ends_with(expected), "{}", message);
+        }
+    }
+
+    #[test]
+    fn declared_tokens_cannot_have_rules() {
+        let error = Grammar::read("%token ID\nS -> ID\nID -> a\n", None, Dialect::Arrow)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("'ID'"), "{}", error);
+    }
+}
@@ -1,75 +1,171 @@
+use super::grammar::{Production, SymbolOrder};
+use super::lexer::quote_terminal;
 use super::Grammar;
 use comfy_table::{presets::UTF8_FULL, Attribute, Cell, ContentArrangement, Table};
+use indexmap::{IndexMap, IndexSet};
 use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
 
+/// Why productions compete for a parsing table cell `M[A, a]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// Two productions `A → α | β` both start with `a`, or both derive ε
+    FirstFirst,
+    /// `A → α` derives ε and `a` is in FOLLOW(A), while another production
+    /// starts with `a`
+    FirstFollow,
+}
+
+/// A parsing table cell claimed by more than one production
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub non_terminal: String,
+    pub terminal: String,
+    /// The competing productions, in grammar order
+    pub productions: Vec<Production>,
+    pub kind: ConflictKind,
+    /// The terminals among the productions' symbols, quoted when displayed
+    pub terminals: IndexSet<String>,
+}
+
+/// Every conflict in the parsing table of a grammar that is not LL(1)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConflictReport {
+    /// The conflicting cells, in the order of the productions claiming them
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A production claiming a parsing table cell `M[A, a]`
+#[derive(Debug, Clone)]
+struct Claim {
+    production: Production,
+    /// Whether `a` is in the FIRST set of the derivation
+    by_first: bool,
+    /// Whether the derivation derives ε
+    nullable: bool,
+}
+
+/// The claims on each cell, in the order of the productions making them
+type Cells = IndexMap<(String, String), Vec<Claim>>;
+
 /// Represents an LL(1) Parsing Table
 #[derive(Debug, Clone)]
 pub struct ParsingTable {
     pub table: HashMap<(String, String), Vec<String>>,
     non_terminals: Vec<String>,
     terminals: Vec<String>,
+    /// EBNF source of helper non-terminals, shown next to their rows
+    helpers: HashMap<String, String>,
 }
 
 impl ParsingTable {
-    /// Build a Parsing Table from a given Grammar
-    pub fn build(grammar: &Grammar) -> Result<Self, String> {
-        let first_sets = grammar.compute_first_sets();
-        let follow_sets = grammar.compute_follow_sets(&first_sets);
+    /// Build a Parsing Table from a given Grammar, or report every cell
+    /// that more than one production claims
+    pub fn build(grammar: &Grammar) -> Result<Self, ConflictReport> {
+        // Columns and rows follow the grammar's order, with $ as the last column
+        let mut terminals_vec: Vec<String> = grammar.terminals.iter().cloned().collect();
+        let mut non_terminals_vec: Vec<String> = grammar.non_terminals.iter().cloned().collect();
+        if grammar.order == SymbolOrder::Sorted {
+            terminals_vec.sort();
+            non_terminals_vec.sort();
+        }
+        terminals_vec.push("$".to_string());
 
-        let mut table: HashMap<(String, String), Vec<String>> = HashMap::new();
-        let mut conflicts = false;
+        let cells = Self::cells(grammar);
+        let report = Self::report(grammar, &cells);
+        if !report.is_empty() {
+            return Err(report);
+        }
 
-        // Add $ to terminals for the parsing table
-        let mut terminals = grammar.terminals.clone();
-        terminals.insert("$".to_string());
+        // Without conflicts every cell holds a single production
+        let table = cells
+            .into_iter()
+            .map(|(key, claims)| (key, claims[0].production.derivation.clone()))
+            .collect();
 
-        // Prepare sorted vectors for display
-        let mut terminals_vec: Vec<String> = terminals.into_iter().collect();
-        terminals_vec.sort();
+        Ok(Self {
+            table,
+            non_terminals: non_terminals_vec,
+            terminals: terminals_vec,
+            helpers: grammar
+                .helpers
+                .iter()
+                .map(|(nt, origin)| (nt.clone(), origin.source.clone()))
+                .collect(),
+        })
+    }
 
-        let mut non_terminals_vec: Vec<String> =
-            grammar.non_terminals.clone().into_iter().collect();
-        non_terminals_vec.sort();
+    /// Every cell of the grammar's parsing table that more than one
+    /// production claims, in grammar order, each with its kind
+    pub fn conflict_report(grammar: &Grammar) -> ConflictReport {
+        Self::report(grammar, &Self::cells(grammar))
+    }
 
-        // Build the parsing table
+    fn report(grammar: &Grammar, cells: &Cells) -> ConflictReport {
+        let conflicts = cells
+            .iter()
+            .filter(|(_, claims)| claims.len() > 1)
+            .map(|((non_terminal, terminal), claims)| {
+                let by_first = claims.iter().filter(|claim| claim.by_first).count();
+                let nullable = claims.iter().filter(|claim| claim.nullable).count();
+                let kind = if by_first > 1 || nullable > 1 {
+                    ConflictKind::FirstFirst
+                } else {
+                    ConflictKind::FirstFollow
+                };
+                let productions: Vec<Production> = claims
+                    .iter()
+                    .map(|claim| claim.production.clone())
+                    .collect();
+                let terminals = productions
+                    .iter()
+                    .flat_map(|production| &production.derivation)
+                    .filter(|symbol| grammar.terminals.contains(*symbol))
+                    .cloned()
+                    .collect();
+                Conflict {
+                    non_terminal: non_terminal.clone(),
+                    terminal: terminal.clone(),
+                    productions,
+                    kind,
+                    terminals,
+                }
+            })
+            .collect();
+        ConflictReport { conflicts }
+    }
+
+    /// The productions claiming each cell `M[A, a]`: those with `a` in the
+    /// FIRST set of their derivation, and those whose derivation derives ε
+    /// when `a` is in FOLLOW(A)
+    fn cells(grammar: &Grammar) -> Cells {
+        let first_sets = grammar.compute_first_sets();
+        let follow_sets = grammar.compute_follow_sets(&first_sets);
+
+        let mut cells = Cells::new();
         for production in &grammar.productions {
             let nt = &production.non_terminal;
             let first_of_rhs = grammar.compute_first_of_string(&production.derivation, &first_sets);
+            let nullable = first_of_rhs.contains("ε");
 
-            for terminal in &first_of_rhs {
-                if terminal != "ε" {
-                    let key = (nt.clone(), terminal.clone());
-                    if table.contains_key(&key) {
-                        conflicts = true;
-                    }
-                    table.insert(key, production.derivation.clone());
-                }
+            let mut lookaheads: IndexSet<&String> =
+                first_of_rhs.iter().filter(|t| *t != "ε").collect();
+            if nullable {
+                lookaheads.extend(follow_sets.get(nt).into_iter().flatten());
             }
-
-            // If ε is in FIRST(α), add production to FOLLOW(A)
-            if first_of_rhs.contains("ε") {
-                if let Some(follow_set) = follow_sets.get(nt) {
-                    for terminal in follow_set {
-                        let key = (nt.clone(), terminal.clone());
-                        if table.contains_key(&key) {
-                            conflicts = true;
-                        }
-                        table.insert(key, production.derivation.clone());
-                    }
+            for terminal in lookaheads {
+                let cell = cells.entry((nt.clone(), terminal.clone())).or_default();
+                if !cell.iter().any(|claim| claim.production == *production) {
+                    cell.push(Claim {
+                        production: production.clone(),
+                        by_first: first_of_rhs.contains(terminal),
+                        nullable,
+                    });
                 }
             }
         }
-
-        if conflicts {
-            return Err("Grammar is not LL(1) - parsing table has conflicts".to_string());
-        }
-
-        Ok(Self {
-            table,
-            non_terminals: non_terminals_vec,
-            terminals: terminals_vec,
-        })
+        cells
     }
 
     /// Display the Parsing Table as a formatted table using comfy_table
@@ -90,7 +186,11 @@ impl ParsingTable {
 
         // Data Rows
         for nt in &self.non_terminals {
-            let mut row = vec![Cell::new(nt.as_str())];
+            let label = match self.helpers.get(nt) {
+                Some(source) => format!("{}\n{}", nt, source),
+                None => nt.clone(),
+            };
+            let mut row = vec![Cell::new(label)];
             for terminal in &self.terminals {
                 let content = self
                     .table
@@ -112,6 +212,83 @@ impl ParsingTable {
     }
 }
 
+impl ConflictReport {
+    pub fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.conflicts.len()
+    }
+
+    /// The conflicts of one kind
+    pub fn of_kind(&self, kind: ConflictKind) -> impl Iterator<Item = &Conflict> {
+        self.conflicts
+            .iter()
+            .filter(move |conflict| conflict.kind == kind)
+    }
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictKind::FirstFirst => write!(f, "FIRST/FIRST"),
+            ConflictKind::FirstFollow => write!(f, "FIRST/FOLLOW"),
+        }
+    }
+}
+
+/// Displays the conflict as `M[A, a] (FIRST/FIRST): A → x | A → y`
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quote = |symbol: &String| {
+            if self.terminals.contains(symbol) {
+                quote_terminal(symbol)
+            } else {
+                symbol.clone()
+            }
+        };
+        let productions: Vec<String> = self
+            .productions
+            .iter()
+            .map(|p| {
+                let derivation: Vec<String> = p.derivation.iter().map(quote).collect();
+                format!("{} → {}", p.non_terminal, derivation.join(" "))
+            })
+            .collect();
+        let terminal = if self.terminal == "$" {
+            self.terminal.clone()
+        } else {
+            quote_terminal(&self.terminal)
+        };
+        write!(
+            f,
+            "M[{}, {}] ({}): {}",
+            self.non_terminal,
+            terminal,
+            self.kind,
+            productions.join(" | ")
+        )
+    }
+}
+
+/// Displays a summary line followed by one conflict per line
+impl fmt::Display for ConflictReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Grammar is not LL(1) - parsing table has {} conflict(s):",
+            self.len()
+        )?;
+        for conflict in &self.conflicts {
+            write!(f, "\n   {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ConflictReport {}
+
 /// Parsing table display
 impl fmt::Display for ParsingTable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -0,0 +1,149 @@
+use indexmap::IndexSet;
+
+use super::{Rewrite, Traced};
+use crate::grammar::Production;
+use crate::Grammar;
+
+impl Grammar {
+    /// Removes every ε-production without changing the language, apart from
+    /// the empty string itself.
+    ///
+    /// Each production is replaced by the variants that leave out any
+    /// combination of its nullable non-terminals, except the variant that
+    /// leaves out everything; variants `A → A` derive nothing and are dropped,
+    /// as are non-terminals that derive only ε.
+    /// When the start symbol `S` is nullable, a fresh start symbol `S'` with
+    /// `S' → S | ε` keeps ε in the language, and is the only symbol that
+    /// derives ε afterwards; it gets `S' → ε` alone when `S` derives only ε.
+    pub fn eliminate_epsilon_productions(&self) -> Rewrite {
+        self.remove_epsilon_productions(true)
+    }
+
+    /// Removes the ε-productions; with `new_start` false, a nullable start
+    /// symbol keeps `S → ε` instead, which is only sound when `S` appears in
+    /// no derivation
+    pub(crate) fn remove_epsilon_productions(&self, new_start: bool) -> Rewrite {
+        let nullable = self.nullable_non_terminals();
+        // Non-terminals that derive only ε are left without productions, so
+        // every variant leaves them out
+        let only_epsilon: IndexSet<String> = self
+            .compute_first_sets()
+            .into_iter()
+            .filter(|(symbol, first)| nullable.contains(symbol) && first.iter().all(|s| s == "ε"))
+            .map(|(symbol, _)| symbol)
+            .collect();
+        let mut traced: Vec<Traced> = Vec::new();
+        let start_origins: Vec<Production> = self
+            .productions
+            .iter()
+            .filter(|p| p.non_terminal == self.start_symbol)
+            .cloned()
+            .collect();
+
+        let mut grammar = self.clone();
+        if nullable.contains(&self.start_symbol) && new_start {
+            let start = self.fresh_non_terminal(&self.start_symbol, &IndexSet::new());
+            // A start symbol deriving only ε is left without productions
+            let derivations = if only_epsilon.contains(&self.start_symbol) {
+                vec!["ε".to_string()]
+            } else {
+                vec![self.start_symbol.clone(), "ε".to_string()]
+            };
+            for derivation in derivations {
+                let production = Production {
+                    non_terminal: start.clone(),
+                    derivation: vec![derivation],
+                    span: None,
+                };
+                traced.push((production, start_origins.clone()));
+            }
+            grammar.start_symbol = start;
+        }
+
+        for production in &self.productions {
+            // Every variant keeps or leaves out each nullable symbol in turn,
+            // the variant keeping them all first
+            let mut variants: Vec<Vec<String>> = vec![Vec::new()];
+            let symbols = production
+                .derivation
+                .iter()
+                .filter(|s| *s != "ε" && !only_epsilon.contains(*s));
+            for symbol in symbols {
+                let without = nullable.contains(symbol).then(|| variants.clone());
+                for variant in variants.iter_mut() {
+                    variant.push(symbol.clone());
+                }
+                variants.extend(without.into_iter().flatten());
+            }
+
+            for derivation in variants {
+                if derivation.is_empty() || derivation == [production.non_terminal.clone()] {
+                    continue;
+                }
+                let variant = Production {
+                    non_terminal: production.non_terminal.clone(),
+                    derivation,
+                    span: production.span,
+                };
+                traced.push((variant, vec![production.clone()]));
+            }
+        }
+
+        if nullable.contains(&self.start_symbol) && !new_start {
+            let production = Production {
+                non_terminal: self.start_symbol.clone(),
+                derivation: vec!["ε".to_string()],
+                span: None,
+            };
+            traced.push((production, start_origins));
+        }
+
+        grammar.rewrite(traced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    #[test]
+    fn leaves_out_every_combination_of_nullable_symbols() {
+        let grammar = Grammar::from_string("S -> A b A\nA -> a | ε\n", "S").unwrap();
+        let rewrite = grammar.eliminate_epsilon_productions();
+        let derivations: Vec<String> = rewrite
+            .grammar
+            .productions
+            .iter()
+            .filter(|p| p.non_terminal == "S")
+            .map(|p| p.derivation.join(" "))
+            .collect();
+        assert_eq!(derivations, ["A b A", "b A", "A b", "b"]);
+    }
+
+    #[test]
+    fn handles_more_nullable_symbols_than_bits_in_a_word() {
+        let mut grammar = Grammar::new("S");
+        let mut derivation = vec!["A"; 70];
+        derivation.push("b");
+        grammar.add_production("S", derivation);
+        grammar.add_production("A", vec!["ε"]);
+        let rewrite = grammar.eliminate_epsilon_productions();
+        assert_eq!(rewrite.grammar.productions.len(), 1);
+        assert_eq!(rewrite.grammar.productions[0].derivation, ["b"]);
+    }
+
+    #[test]
+    fn start_symbol_deriving_only_epsilon_leaves_no_dangling_symbol() {
+        let grammar = Grammar::from_string("S -> ε\n", "S").unwrap();
+        let rewrite = grammar.eliminate_epsilon_productions();
+        let rules: Vec<(String, String)> = rewrite
+            .grammar
+            .productions
+            .iter()
+            .map(|p| (p.non_terminal.clone(), p.derivation.join(" ")))
+            .collect();
+        assert_eq!(rules, [("S'".to_string(), "ε".to_string())]);
+        assert_eq!(rewrite.grammar.start_symbol, "S'");
+        assert!(!rewrite.grammar.non_terminals.contains("S"));
+    }
+}
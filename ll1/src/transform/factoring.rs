@@ -0,0 +1,167 @@
+use indexmap::{IndexMap, IndexSet};
+
+use super::{Rewrite, Traced};
+use crate::grammar::Production;
+use crate::Grammar;
+
+impl Grammar {
+    /// Non-terminals with two alternatives that start with the same symbol
+    pub fn non_terminals_needing_factoring(&self) -> IndexSet<String> {
+        self.traced_rules()
+            .into_iter()
+            .filter(|(_, alternatives)| {
+                let mut firsts = IndexSet::new();
+                alternatives
+                    .iter()
+                    .filter_map(|(production, _)| Self::leading_symbol(production))
+                    .any(|first| !firsts.insert(first))
+            })
+            .map(|(non_terminal, _)| non_terminal)
+            .collect()
+    }
+
+    /// Left-factors the grammar until no two alternatives of a non-terminal
+    /// start with the same symbol.
+    ///
+    /// Alternatives `A → α β₁ | … | α βₙ` that share their longest common
+    /// prefix `α` become `A → α A'` and `A' → β₁ | … | βₙ`, where `A'` is a
+    /// fresh non-terminal and an empty `βᵢ` is ε. The new rules are factored
+    /// in turn, so `S → if E then S | if E then S else S` becomes
+    /// `S → if E then S S'` and `S' → ε | else S`.
+    pub fn left_factor(&self) -> Rewrite {
+        let mut taken = IndexSet::new();
+        let mut traced = Vec::new();
+        for (non_terminal, alternatives) in self.traced_rules() {
+            self.factor_rule(&non_terminal, alternatives, &mut taken, &mut traced);
+        }
+        self.rewrite(traced)
+    }
+
+    /// Factors the alternatives of `non_terminal` into `out`, each fresh
+    /// rule right after the rule it was pulled out of
+    fn factor_rule(
+        &self,
+        non_terminal: &str,
+        alternatives: Vec<Traced>,
+        taken: &mut IndexSet<String>,
+        out: &mut Vec<Traced>,
+    ) {
+        // Alternatives grouped by their first symbol, with ε-productions under `None`
+        let mut groups: IndexMap<Option<String>, Vec<Traced>> = IndexMap::new();
+        for (production, origins) in alternatives {
+            let key = Self::leading_symbol(&production).cloned();
+            groups.entry(key).or_default().push((production, origins));
+        }
+
+        let mut fresh_rules = Vec::new();
+        for (first, group) in groups {
+            if first.is_none() || group.len() == 1 {
+                out.extend(group);
+                continue;
+            }
+
+            let prefix = Self::common_prefix(&group);
+            let tail = self.fresh_non_terminal(non_terminal, taken);
+            taken.insert(tail.clone());
+
+            let mut derivation = prefix.clone();
+            derivation.push(tail.clone());
+            let mut merged = Vec::new();
+            for (_, origins) in &group {
+                for origin in origins {
+                    if !merged.contains(origin) {
+                        merged.push(origin.clone());
+                    }
+                }
+            }
+            out.push((
+                Production {
+                    non_terminal: non_terminal.to_string(),
+                    derivation,
+                    span: group[0].0.span,
+                },
+                merged,
+            ));
+
+            let suffixes = group
+                .into_iter()
+                .map(|(production, origins)| {
+                    let mut derivation = production.derivation[prefix.len()..].to_vec();
+                    if derivation.is_empty() {
+                        derivation.push("ε".to_string());
+                    }
+                    let suffix = Production {
+                        non_terminal: tail.clone(),
+                        derivation,
+                        span: production.span,
+                    };
+                    (suffix, origins)
+                })
+                .collect::<Vec<_>>();
+            fresh_rules.push((tail, suffixes));
+        }
+
+        for (tail, suffixes) in fresh_rules {
+            self.factor_rule(&tail, suffixes, taken, out);
+        }
+    }
+
+    /// Longest prefix shared by every alternative of `group`
+    fn common_prefix(group: &[Traced]) -> Vec<String> {
+        let first = &group[0].0.derivation;
+        let len = group[1..].iter().fold(first.len(), |len, (production, _)| {
+            first
+                .iter()
+                .zip(&production.derivation)
+                .take(len)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        first[..len].to_vec()
+    }
+
+    /// The first symbol of a production, or `None` for an ε-production
+    fn leading_symbol(production: &Production) -> Option<&String> {
+        production
+            .derivation
+            .first()
+            .filter(|symbol| *symbol != "ε")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    fn rules(grammar: &Grammar) -> Vec<String> {
+        grammar
+            .productions
+            .iter()
+            .map(|p| format!("{} → {}", p.non_terminal, p.derivation.join(" ")))
+            .collect()
+    }
+
+    #[test]
+    fn factors_a_prefix_shared_by_three_alternatives() {
+        let grammar = Grammar::from_string("S -> a b c | a b d | a b | x\n", "S").unwrap();
+        assert_eq!(grammar.non_terminals_needing_factoring().len(), 1);
+
+        let rewrite = grammar.left_factor();
+        assert_eq!(
+            rules(&rewrite.grammar),
+            ["S → a b S'", "S → x", "S' → c", "S' → d", "S' → ε"]
+        );
+        assert!(rewrite.grammar.non_terminals_needing_factoring().is_empty());
+        assert!(rewrite.grammar.is_ll1());
+    }
+
+    #[test]
+    fn names_nested_helpers_with_more_primes() {
+        let grammar = Grammar::from_string("S -> a b c | a b d | a e\n", "S").unwrap();
+        let rewrite = grammar.left_factor();
+        assert_eq!(
+            rules(&rewrite.grammar),
+            ["S → a S'", "S' → b S''", "S' → e", "S'' → c", "S'' → d"]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use super::{Rewrite, Traced};
+use crate::error::GrammarError;
+use crate::grammar::Production;
+use crate::Grammar;
+
+impl Grammar {
+    /// Replaces every occurrence of `non_terminal` by each of its
+    /// alternatives and drops its rules, as in `A → x B y` with `B → b | ε`
+    /// becoming `A → x b y | x y`.
+    ///
+    /// A derivation using `non_terminal` n times is expanded into every
+    /// combination of its alternatives. Symbols that no longer occur leave
+    /// `terminals` and `non_terminals`.
+    ///
+    /// Fails when `non_terminal` is not a non-terminal of the grammar, is the
+    /// start symbol, or appears in its own alternatives.
+    pub fn inline(&self, non_terminal: &str) -> Result<Rewrite, GrammarError> {
+        let cannot_inline = |reason: &str| GrammarError::CannotInline {
+            symbol: non_terminal.to_string(),
+            reason: reason.to_string(),
+        };
+        if !self.non_terminals.contains(non_terminal) {
+            return Err(cannot_inline("is not a non-terminal of the grammar"));
+        }
+        if non_terminal == self.start_symbol {
+            return Err(cannot_inline("is the start symbol"));
+        }
+
+        let rules = self.traced_rules();
+        let alternatives = rules
+            .get(non_terminal)
+            .ok_or_else(|| cannot_inline("has no rule"))?;
+        let recursive = alternatives
+            .iter()
+            .any(|(production, _)| production.derivation.iter().any(|s| s == non_terminal));
+        if recursive {
+            return Err(cannot_inline("is recursive"));
+        }
+
+        let mut traced: Vec<Traced> = Vec::new();
+        for (production, origins) in rules.values().flatten() {
+            if production.non_terminal == non_terminal {
+                continue;
+            }
+
+            // Every combination of alternatives for the occurrences so far
+            let mut expansions: Vec<(Vec<String>, Vec<Production>)> =
+                vec![(Vec::new(), origins.clone())];
+            for symbol in &production.derivation {
+                if symbol != non_terminal {
+                    for (derivation, _) in expansions.iter_mut() {
+                        derivation.push(symbol.clone());
+                    }
+                    continue;
+                }
+                expansions = expansions
+                    .iter()
+                    .flat_map(|(derivation, origins)| {
+                        alternatives.iter().map(|(alternative, more_origins)| {
+                            let mut derivation = derivation.clone();
+                            derivation.extend(alternative.derivation.iter().cloned());
+                            let mut merged = origins.clone();
+                            merged.extend(more_origins.iter().cloned());
+                            (derivation, merged)
+                        })
+                    })
+                    .collect();
+            }
+
+            for (derivation, origins) in expansions {
+                let mut derivation: Vec<String> =
+                    derivation.into_iter().filter(|s| s != "ε").collect();
+                if derivation.is_empty() {
+                    derivation.push("ε".to_string());
+                }
+                traced.push((
+                    Production {
+                        non_terminal: production.non_terminal.clone(),
+                        derivation,
+                        span: production.span,
+                    },
+                    origins,
+                ));
+            }
+        }
+
+        Ok(self.rewrite(traced))
+    }
+
+    /// Pulls `sequence` out into a new rule `name → sequence`, replacing
+    /// every occurrence of it, left to right and without overlaps, by `name`.
+    ///
+    /// The new rule comes right after the rules of the first non-terminal
+    /// that uses the sequence.
+    ///
+    /// Fails when `sequence` is empty, holds a symbol that is not in the
+    /// grammar, or occurs in no derivation, and when `name` is already a
+    /// symbol of the grammar or is reserved.
+    pub fn extract_rule(&self, sequence: &[&str], name: &str) -> Result<Rewrite, GrammarError> {
+        let cannot_extract = |reason: String| GrammarError::CannotExtract {
+            sequence: sequence.iter().map(|s| s.to_string()).collect(),
+            reason,
+        };
+        if sequence.is_empty() {
+            return Err(cannot_extract("the sequence is empty".to_string()));
+        }
+        if let Some(unknown) = sequence
+            .iter()
+            .find(|s| !self.terminals.contains(**s) && !self.non_terminals.contains(**s))
+        {
+            return Err(cannot_extract(format!(
+                "'{}' is not a symbol of the grammar",
+                unknown
+            )));
+        }
+        if name.is_empty() || name == "ε" || name == "$" {
+            return Err(cannot_extract(format!("'{}' is reserved", name)));
+        }
+        if self.terminals.contains(name) || self.non_terminals.contains(name) {
+            return Err(cannot_extract(format!(
+                "'{}' is already a symbol of the grammar",
+                name
+            )));
+        }
+
+        let extracted = Production {
+            non_terminal: name.to_string(),
+            derivation: sequence.iter().map(|s| s.to_string()).collect(),
+            span: None,
+        };
+        let mut extracted_origins: Vec<Production> = Vec::new();
+        let mut traced: Vec<Traced> = Vec::new();
+        let mut insert_at = None;
+
+        for (non_terminal, alternatives) in self.traced_rules() {
+            for (production, origins) in alternatives {
+                let symbols = &production.derivation;
+                let mut derivation: Vec<String> = Vec::new();
+                let mut i = 0;
+                while i < symbols.len() {
+                    let matches = symbols[i..].len() >= sequence.len()
+                        && symbols[i..i + sequence.len()]
+                            .iter()
+                            .zip(sequence)
+                            .all(|(symbol, wanted)| symbol == wanted);
+                    if matches {
+                        derivation.push(name.to_string());
+                        i += sequence.len();
+                    } else {
+                        derivation.push(symbols[i].clone());
+                        i += 1;
+                    }
+                }
+
+                if derivation == *symbols {
+                    traced.push((production, origins));
+                    continue;
+                }
+                for origin in &origins {
+                    if !extracted_origins.contains(origin) {
+                        extracted_origins.push(origin.clone());
+                    }
+                }
+                traced.push((
+                    Production {
+                        non_terminal: non_terminal.clone(),
+                        derivation,
+                        span: production.span,
+                    },
+                    origins,
+                ));
+            }
+            if insert_at.is_none() && !extracted_origins.is_empty() {
+                insert_at = Some(traced.len());
+            }
+        }
+
+        let Some(insert_at) = insert_at else {
+            return Err(cannot_extract("it occurs in no derivation".to_string()));
+        };
+        traced.insert(insert_at, (extracted, extracted_origins));
+        Ok(self.rewrite(traced))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GrammarError;
+    use crate::Grammar;
+
+    const SOURCE: &str = "S -> x B y | B\nB -> b | ε\n";
+
+    fn rules(grammar: &Grammar) -> Vec<String> {
+        grammar
+            .productions
+            .iter()
+            .map(|p| format!("{} → {}", p.non_terminal, p.derivation.join(" ")))
+            .collect()
+    }
+
+    fn reason(error: GrammarError) -> String {
+        match error {
+            GrammarError::CannotInline { reason, .. }
+            | GrammarError::CannotExtract { reason, .. } => reason,
+            error => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[test]
+    fn inlines_every_alternative() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let rewrite = grammar.inline("B").unwrap();
+        assert_eq!(
+            rules(&rewrite.grammar),
+            ["S → x b y", "S → x y", "S → b", "S → ε"]
+        );
+        assert!(!rewrite.grammar.non_terminals.contains("B"));
+        assert_eq!(rewrite.origins[&rewrite.grammar.productions[0]].len(), 2);
+    }
+
+    #[test]
+    fn cannot_inline_the_start_symbol_or_a_missing_one() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        assert_eq!(
+            reason(grammar.inline("S").unwrap_err()),
+            "is the start symbol"
+        );
+        assert_eq!(
+            reason(grammar.inline("C").unwrap_err()),
+            "is not a non-terminal of the grammar"
+        );
+    }
+
+    #[test]
+    fn extracts_a_sequence_into_a_new_rule() {
+        let grammar = Grammar::from_string("S -> x B y | B y\nB -> b\n", "S").unwrap();
+        let rewrite = grammar.extract_rule(&["B", "y"], "C").unwrap();
+        assert_eq!(
+            rules(&rewrite.grammar),
+            ["S → x C", "S → C", "C → B y", "B → b"]
+        );
+        let extracted = &rewrite.grammar.productions[2];
+        assert_eq!(rewrite.origins[extracted], &grammar.productions[..2]);
+    }
+
+    #[test]
+    fn cannot_extract_into_the_start_symbol_or_from_missing_symbols() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        assert_eq!(
+            reason(grammar.extract_rule(&["x", "B"], "S").unwrap_err()),
+            "'S' is already a symbol of the grammar"
+        );
+        assert_eq!(
+            reason(grammar.extract_rule(&["x", "C"], "D").unwrap_err()),
+            "'C' is not a symbol of the grammar"
+        );
+        assert_eq!(
+            reason(grammar.extract_rule(&["y", "x"], "D").unwrap_err()),
+            "it occurs in no derivation"
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use indexmap::{IndexMap, IndexSet};
+
+use super::{Rewrite, Traced};
+use crate::grammar::Production;
+use crate::Grammar;
+
+/// The result of `Grammar::eliminate_left_recursion`
+#[derive(Debug, Clone)]
+pub struct LeftRecursionRemoval {
+    pub rewrite: Rewrite,
+    /// One left-recursive cycle for each group of mutually left-recursive
+    /// non-terminals of the original grammar that was removed, listed from
+    /// the earliest non-terminal in the ordering: `[A, B]` is `A ⇒ B … ⇒ A …`
+    pub broken_cycles: Vec<Vec<String>>,
+    /// Non-terminals that are still left-recursive in the rewritten grammar
+    pub remaining: IndexSet<String>,
+}
+
+impl Grammar {
+    /// Non-terminals with a production that starts with the non-terminal itself
+    pub fn directly_left_recursive_non_terminals(&self) -> IndexSet<String> {
+        self.productions
+            .iter()
+            .filter(|production| Self::is_directly_left_recursive(production))
+            .map(|production| production.non_terminal.clone())
+            .collect()
+    }
+
+    /// Non-terminals `A` with a derivation `A ⇒+ A α`, directly, through other
+    /// non-terminals, or behind non-terminals that derive ε
+    pub fn left_recursive_non_terminals(&self) -> IndexSet<String> {
+        let corners = self.left_corners();
+        self.non_terminals
+            .iter()
+            .filter(|nt| Self::reaches(&corners, nt, nt))
+            .cloned()
+            .collect()
+    }
+
+    /// Rewrites direct left recursion into right recursion.
+    ///
+    /// The rules `A → A α₁ | … | A αₙ | β₁ | … | βₘ` become
+    /// `A → β₁ A' | … | βₘ A'` and `A' → α₁ A' | … | αₙ A' | ε`, where `A'` is
+    /// a fresh non-terminal. Productions `A → A` derive nothing new and are
+    /// dropped. A non-terminal whose alternatives are all left-recursive
+    /// derives no terminal string and is left unchanged.
+    pub fn eliminate_direct_left_recursion(&self) -> Rewrite {
+        let mut taken = IndexSet::new();
+        let mut traced = Vec::new();
+        for (non_terminal, alternatives) in self.traced_rules() {
+            traced.extend(self.split_left_recursion(
+                &non_terminal,
+                alternatives,
+                &mut taken,
+                false,
+            ));
+        }
+        self.rewrite(traced)
+    }
+
+    /// Removes direct and indirect left recursion with Paull's algorithm,
+    /// taking the non-terminals in grammar order
+    pub fn eliminate_left_recursion(&self) -> LeftRecursionRemoval {
+        self.eliminate_left_recursion_in(&[])
+    }
+
+    /// Removes direct and indirect left recursion with Paull's algorithm.
+    ///
+    /// Non-terminals are taken in `order`, followed by the ones it leaves out
+    /// in grammar order. For each non-terminal `Aᵢ`, productions `Aᵢ → Aⱼ γ`
+    /// with `j < i` are replaced by `Aⱼ`'s alternatives followed by `γ`, and
+    /// the direct left recursion this exposes is rewritten as in
+    /// `eliminate_direct_left_recursion`. When a substituted alternative is ε,
+    /// the symbol after it is examined in turn.
+    ///
+    /// A non-terminal that derives ε in front of a later one can hide left
+    /// recursion the ordering never exposes, as `A → B A x` with `B → ε`.
+    /// Such non-terminals, those with only left-recursive alternatives and
+    /// those whose productions start with one of them are listed in
+    /// `remaining`; removing the ε-productions first avoids the first kind.
+    pub fn eliminate_left_recursion_in(&self, order: &[&str]) -> LeftRecursionRemoval {
+        let mut ordering: IndexSet<String> = order
+            .iter()
+            .filter(|nt| self.non_terminals.contains(**nt))
+            .map(|nt| nt.to_string())
+            .collect();
+        ordering.extend(self.non_terminals.iter().cloned());
+
+        let mut rules = self.traced_rules();
+        let mut tails: IndexMap<String, Vec<Traced>> = IndexMap::new();
+        let mut taken = IndexSet::new();
+
+        for (i, non_terminal) in ordering.iter().enumerate() {
+            let Some(mut alternatives) = rules.get(non_terminal).cloned() else {
+                continue;
+            };
+
+            let mut changed = true;
+            while changed {
+                changed = false;
+                let mut substituted = Vec::new();
+                for (production, origins) in alternatives {
+                    let mut symbols = production.derivation.iter().filter(|s| *s != "ε");
+                    // A lead whose rules are all left-recursive was left as
+                    // it is, and substituting it would never end
+                    let earlier = symbols
+                        .next()
+                        .filter(|lead| ordering.get_index_of(*lead).is_some_and(|j| j < i))
+                        .and_then(|lead| rules.get(lead))
+                        .filter(|earlier| {
+                            !earlier
+                                .iter()
+                                .any(|(p, _)| Self::is_directly_left_recursive(p))
+                        });
+                    let Some(earlier) = earlier else {
+                        substituted.push((production, origins));
+                        continue;
+                    };
+
+                    let rest: Vec<String> = symbols.cloned().collect();
+                    for (alternative, more_origins) in earlier {
+                        let mut derivation: Vec<String> = alternative
+                            .derivation
+                            .iter()
+                            .filter(|s| *s != "ε")
+                            .chain(&rest)
+                            .cloned()
+                            .collect();
+                        if derivation.is_empty() {
+                            derivation.push("ε".to_string());
+                        }
+                        let mut merged = origins.clone();
+                        for origin in more_origins {
+                            if !merged.contains(origin) {
+                                merged.push(origin.clone());
+                            }
+                        }
+                        substituted.push((
+                            Production {
+                                non_terminal: non_terminal.clone(),
+                                derivation,
+                                span: production.span,
+                            },
+                            merged,
+                        ));
+                    }
+                    changed = true;
+                }
+                alternatives = substituted;
+            }
+
+            let split = self.split_left_recursion(non_terminal, alternatives, &mut taken, true);
+            let (own, tail): (Vec<Traced>, Vec<Traced>) = split
+                .into_iter()
+                .partition(|(production, _)| &production.non_terminal == non_terminal);
+            rules.insert(non_terminal.clone(), own);
+            tails.insert(non_terminal.clone(), tail);
+        }
+
+        let mut traced = Vec::new();
+        for (non_terminal, alternatives) in rules {
+            traced.extend(alternatives);
+            traced.extend(tails.shift_remove(&non_terminal).unwrap_or_default());
+        }
+        let rewrite = self.rewrite(traced);
+        let remaining = rewrite.grammar.left_recursive_non_terminals();
+
+        let mut broken_cycles = Self::cycles_in(&self.left_corners(), &ordering);
+        broken_cycles.retain(|cycle| !cycle.iter().any(|nt| remaining.contains(nt)));
+
+        LeftRecursionRemoval {
+            rewrite,
+            broken_cycles,
+            remaining,
+        }
+    }
+
+    /// Rewrites the direct left recursion among `alternatives` of `non_terminal`,
+    /// returning its new productions followed by those of the fresh tail.
+    ///
+    /// With `keep_nullable_tail_out`, an ε base alternative becomes the
+    /// alternatives `α₁ A' | … | αₙ A' | ε` instead of `A → A'`, so that no
+    /// production of `A` starts with the nullable tail. Since `A` derives ε,
+    /// the leading `A`s of each `αᵢ` are dropped, and an `αᵢ` made only of
+    /// `A`s adds nothing and gives no alternative.
+    fn split_left_recursion(
+        &self,
+        non_terminal: &str,
+        alternatives: Vec<Traced>,
+        taken: &mut IndexSet<String>,
+        keep_nullable_tail_out: bool,
+    ) -> Vec<Traced> {
+        let (recursive, others): (Vec<Traced>, Vec<Traced>) = alternatives
+            .into_iter()
+            .partition(|(production, _)| Self::is_directly_left_recursive(production));
+        let recursive: Vec<Traced> = if others.is_empty() {
+            recursive
+        } else {
+            recursive
+                .into_iter()
+                .filter(|(production, _)| production.derivation.len() > 1)
+                .collect()
+        };
+        if recursive.is_empty() || others.is_empty() {
+            return recursive.into_iter().chain(others).collect();
+        }
+
+        let tail = self.fresh_non_terminal(non_terminal, taken);
+        taken.insert(tail.clone());
+
+        let mut heads = Vec::new();
+        let mut tails = Vec::new();
+        for (production, origins) in &recursive {
+            let mut derivation = production.derivation[1..].to_vec();
+            derivation.push(tail.clone());
+            tails.push((
+                Production {
+                    non_terminal: tail.clone(),
+                    derivation,
+                    span: production.span,
+                },
+                origins.clone(),
+            ));
+        }
+
+        for (production, origins) in others {
+            let mut derivation: Vec<String> = production
+                .derivation
+                .iter()
+                .filter(|symbol| *symbol != "ε")
+                .cloned()
+                .collect();
+            if derivation.is_empty() && keep_nullable_tail_out {
+                for (tail_production, tail_origins) in &tails {
+                    let derivation: Vec<String> = tail_production
+                        .derivation
+                        .iter()
+                        .skip_while(|symbol| *symbol == non_terminal)
+                        .cloned()
+                        .collect();
+                    if derivation.len() == 1 {
+                        continue;
+                    }
+                    let mut merged = origins.clone();
+                    merged.extend(tail_origins.iter().cloned());
+                    heads.push((
+                        Production {
+                            non_terminal: non_terminal.to_string(),
+                            derivation,
+                            span: tail_production.span,
+                        },
+                        merged,
+                    ));
+                }
+                heads.push((production, origins));
+                continue;
+            }
+            derivation.push(tail.clone());
+            heads.push((
+                Production {
+                    non_terminal: non_terminal.to_string(),
+                    derivation,
+                    span: production.span,
+                },
+                origins,
+            ));
+        }
+
+        tails.push((
+            Production {
+                non_terminal: tail,
+                derivation: vec!["ε".to_string()],
+                span: None,
+            },
+            recursive
+                .into_iter()
+                .flat_map(|(_, origins)| origins)
+                .collect(),
+        ));
+        heads.extend(tails);
+        heads
+    }
+
+    /// For each non-terminal, the non-terminals that can begin one of its
+    /// derivations in one step, behind symbols that derive ε
+    fn left_corners(&self) -> IndexMap<String, IndexSet<String>> {
+        let first_sets = self.compute_first_sets();
+        let nullable = |symbol: &String| first_sets.get(symbol).is_some_and(|s| s.contains("ε"));
+
+        let mut corners: IndexMap<String, IndexSet<String>> = IndexMap::new();
+        for production in &self.productions {
+            let entry = corners.entry(production.non_terminal.clone()).or_default();
+            for symbol in production.derivation.iter().filter(|s| *s != "ε") {
+                if self.non_terminals.contains(symbol) {
+                    entry.insert(symbol.clone());
+                }
+                if !nullable(symbol) {
+                    break;
+                }
+            }
+        }
+        corners
+    }
+
+    fn is_directly_left_recursive(production: &Production) -> bool {
+        production.derivation.first() == Some(&production.non_terminal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transform::Rewrite;
+    use crate::Grammar;
+
+    fn grammar(input: &str) -> Grammar {
+        let start = input.split_whitespace().next().unwrap();
+        Grammar::from_string(input, start).unwrap()
+    }
+
+    #[test]
+    fn leaves_a_lead_with_only_left_recursive_alternatives() {
+        let removal = grammar("S -> X | Y\nX -> X a\nY -> X b\n").eliminate_left_recursion();
+        assert!(removal.remaining.contains("X"));
+        assert!(removal
+            .rewrite
+            .grammar
+            .productions
+            .iter()
+            .any(|p| p.non_terminal == "Y" && p.derivation == ["X", "b"]));
+    }
+
+    #[test]
+    fn leaves_an_earlier_lead_with_only_left_recursive_alternatives() {
+        let removal = grammar("S -> A b | c\nA -> A a\n").eliminate_left_recursion_in(&["A", "S"]);
+        assert!(removal.remaining.contains("A"));
+        assert_eq!(removal.rewrite.grammar.productions.len(), 3);
+    }
+
+    #[test]
+    fn epsilon_base_alternatives_do_not_start_with_the_non_terminal() {
+        let removal = grammar("S -> a S b | S S | ε\n").eliminate_left_recursion();
+        let grammar = &removal.rewrite.grammar;
+        assert!(grammar
+            .productions
+            .iter()
+            .filter(|p| p.non_terminal == "S")
+            .all(|p| p.derivation[0] != "S"));
+        assert!(!grammar
+            .directly_left_recursive_non_terminals()
+            .contains("S"));
+    }
+
+    /// Each production of the rewrite with the productions it came from
+    fn origins(rewrite: &Rewrite) -> Vec<(String, Vec<String>)> {
+        rewrite
+            .origins
+            .iter()
+            .map(|(production, origins)| {
+                let from = origins.iter().map(|origin| origin.to_string()).collect();
+                (production.to_string(), from)
+            })
+            .collect()
+    }
+
+    fn traced(production: &str, from: &[&str]) -> (String, Vec<String>) {
+        (
+            production.to_string(),
+            from.iter().map(|origin| origin.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn removes_direct_left_recursion() {
+        let rewrite = grammar("E -> E '+' T | T\nT -> id\n").eliminate_direct_left_recursion();
+        assert_eq!(
+            origins(&rewrite),
+            [
+                traced("E → T E'", &["E → T"]),
+                traced("E' → + T E'", &["E → E + T"]),
+                traced("E' → ε", &["E → E + T"]),
+                traced("T → id", &["T → id"]),
+            ]
+        );
+        assert!(rewrite.grammar.left_recursive_non_terminals().is_empty());
+    }
+
+    #[test]
+    fn removes_indirect_left_recursion() {
+        let removal = grammar("S -> A a | b\nA -> S c | d\n").eliminate_left_recursion();
+        assert_eq!(removal.broken_cycles, [["S", "A"]]);
+        assert!(removal.remaining.is_empty());
+        assert_eq!(
+            origins(&removal.rewrite),
+            [
+                traced("S → A a", &["S → A a"]),
+                traced("S → b", &["S → b"]),
+                traced("A → b c A'", &["A → S c", "S → b"]),
+                traced("A → d A'", &["A → d"]),
+                traced("A' → a c A'", &["A → S c", "S → A a"]),
+                traced("A' → ε", &["A → S c", "S → A a"]),
+            ]
+        );
+        assert!(removal
+            .rewrite
+            .grammar
+            .left_recursive_non_terminals()
+            .is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+
+use indexmap::IndexSet;
+
+use super::{Rewrite, Traced};
+use crate::grammar::Production;
+use crate::table::Conflict;
+use crate::{Grammar, ParsingTable};
+
+/// Upper bound on the rounds of `Grammar::ll1ify`, in case transforms keep
+/// undoing each other
+const MAX_ROUNDS: usize = 16;
+
+/// A transform applied by `Grammar::ll1ify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineTransform {
+    RemoveUselessSymbols,
+    EliminateLeftRecursion,
+    LeftFactor,
+    /// Replaces `symbol` at the start of `into`'s alternatives by its own alternatives
+    Substitute {
+        symbol: String,
+        into: String,
+    },
+}
+
+/// One transform of the pipeline and what it did
+#[derive(Debug, Clone)]
+pub struct PipelineStep {
+    pub transform: PipelineTransform,
+    /// What changed, one line each
+    pub notes: Vec<String>,
+    /// Parsing table conflicts left after the step
+    pub conflicts: Vec<Conflict>,
+}
+
+/// The result of `Grammar::ll1ify`
+#[derive(Debug, Clone)]
+pub struct Ll1ification {
+    /// The final grammar, traced back to the original productions
+    pub rewrite: Rewrite,
+    pub steps: Vec<PipelineStep>,
+    /// Conflicts none of the transforms could remove
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Grammar {
+    /// Transforms the grammar towards LL(1).
+    ///
+    /// Useless symbols are removed first. Then, until the parsing table builds
+    /// without conflicts, left recursion is eliminated, the grammar is left
+    /// factored and, when conflicts remain, a non-terminal at the start of a
+    /// conflicting rule's alternative is substituted into it and the result
+    /// factored again; a substitution is only kept when it leaves fewer
+    /// conflicts, and symbols it leaves unused are removed. The pipeline stops
+    /// when no transform helps.
+    ///
+    /// Conflicts that remain cannot be removed by these transforms, as for
+    /// ambiguous grammars such as the dangling `else`, whose language has no
+    /// LL(1) grammar.
+    pub fn ll1ify(&self) -> Ll1ification {
+        let mut steps = Vec::new();
+
+        let reduction = self.remove_useless_symbols();
+        let mut rewrite = Rewrite::unchanged(&reduction.grammar);
+        if !reduction.removed_symbols.is_empty() {
+            steps.push(PipelineStep {
+                transform: PipelineTransform::RemoveUselessSymbols,
+                notes: reduction
+                    .removed_symbols
+                    .iter()
+                    .map(|removed| removed.to_string())
+                    .collect(),
+                conflicts: ParsingTable::conflict_report(&rewrite.grammar).conflicts,
+            });
+        }
+
+        for _ in 0..MAX_ROUNDS {
+            if ParsingTable::build(&rewrite.grammar).is_ok() {
+                break;
+            }
+            let mut progressed = false;
+
+            let left_recursive = rewrite.grammar.left_recursive_non_terminals();
+            if !left_recursive.is_empty() {
+                // Every pass adds fresh tails, so only a pass that leaves
+                // fewer left-recursive non-terminals counts as progress
+                let removal = rewrite.grammar.eliminate_left_recursion();
+                if removal.remaining.len() < left_recursive.len() {
+                    let mut notes: Vec<String> = removal
+                        .broken_cycles
+                        .iter()
+                        .map(|cycle| {
+                            format!("Broke the cycle {} → {}", cycle.join(" → "), cycle[0])
+                        })
+                        .collect();
+                    notes.extend(
+                        removal
+                            .remaining
+                            .iter()
+                            .map(|nt| format!("{} is still left-recursive", nt)),
+                    );
+                    rewrite = rewrite.then(removal.rewrite);
+                    steps.push(Self::step(
+                        PipelineTransform::EliminateLeftRecursion,
+                        notes,
+                        &rewrite,
+                    ));
+                    progressed = true;
+                }
+            }
+
+            let unfactored = rewrite.grammar.non_terminals_needing_factoring();
+            if !unfactored.is_empty() {
+                let notes = unfactored
+                    .iter()
+                    .map(|nt| format!("Factored the alternatives of {}", nt))
+                    .collect();
+                rewrite = rewrite.then(rewrite.grammar.left_factor());
+                steps.push(Self::step(PipelineTransform::LeftFactor, notes, &rewrite));
+                progressed = true;
+            }
+
+            if ParsingTable::build(&rewrite.grammar).is_ok() {
+                break;
+            }
+            if !progressed {
+                match rewrite.grammar.best_substitution() {
+                    Some((transform, substituted)) => {
+                        let mut notes = vec![format!(
+                            "Conflicts went from {} to {}",
+                            ParsingTable::conflict_report(&rewrite.grammar).len(),
+                            ParsingTable::conflict_report(&substituted.grammar).len()
+                        )];
+                        rewrite = rewrite.then(substituted);
+
+                        // The substituted rule may no longer be used anywhere
+                        let reduction = rewrite.grammar.remove_useless_symbols();
+                        notes.extend(
+                            reduction
+                                .removed_symbols
+                                .iter()
+                                .map(|removed| format!("Removed {}", removed)),
+                        );
+                        rewrite = rewrite.then(Rewrite::unchanged(&reduction.grammar));
+                        steps.push(Self::step(transform, notes, &rewrite));
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        Ll1ification {
+            conflicts: ParsingTable::conflict_report(&rewrite.grammar).conflicts,
+            rewrite,
+            steps,
+        }
+    }
+
+    fn step(transform: PipelineTransform, notes: Vec<String>, rewrite: &Rewrite) -> PipelineStep {
+        PipelineStep {
+            transform,
+            notes,
+            conflicts: ParsingTable::conflict_report(&rewrite.grammar).conflicts,
+        }
+    }
+
+    /// The first substitution into a conflicting rule that, once the result is
+    /// left-factored, leaves fewer conflicts
+    fn best_substitution(&self) -> Option<(PipelineTransform, Rewrite)> {
+        let conflicts = ParsingTable::conflict_report(self).conflicts;
+        let conflicting: IndexSet<&String> = conflicts.iter().map(|c| &c.non_terminal).collect();
+
+        for into in conflicting {
+            let leading: IndexSet<&String> = self
+                .productions
+                .iter()
+                .filter(|production| &production.non_terminal == into)
+                .filter_map(|production| production.derivation.first())
+                .filter(|symbol| {
+                    *symbol != into
+                        && self
+                            .productions
+                            .iter()
+                            .any(|production| &production.non_terminal == *symbol)
+                })
+                .collect();
+
+            for symbol in leading {
+                let substituted = self.substitute_leading(symbol, into);
+                let factored = substituted.then(substituted.grammar.left_factor());
+                if ParsingTable::conflict_report(&factored.grammar).len() < conflicts.len() {
+                    let transform = PipelineTransform::Substitute {
+                        symbol: symbol.clone(),
+                        into: into.clone(),
+                    };
+                    return Some((transform, factored));
+                }
+            }
+        }
+        None
+    }
+
+    /// Replaces `symbol` at the start of `into`'s alternatives by each of its
+    /// own alternatives
+    fn substitute_leading(&self, symbol: &str, into: &str) -> Rewrite {
+        let rules = self.traced_rules();
+        let mut traced: Vec<Traced> = Vec::new();
+
+        for (production, origins) in rules.values().flatten() {
+            let leads = production.derivation.first().is_some_and(|s| s == symbol);
+            if production.non_terminal != into || !leads {
+                traced.push((production.clone(), origins.clone()));
+                continue;
+            }
+            for (alternative, more_origins) in rules.get(symbol).into_iter().flatten() {
+                let mut derivation: Vec<String> = alternative
+                    .derivation
+                    .iter()
+                    .chain(&production.derivation[1..])
+                    .filter(|s| *s != "ε")
+                    .cloned()
+                    .collect();
+                if derivation.is_empty() {
+                    derivation.push("ε".to_string());
+                }
+                let mut merged = origins.clone();
+                merged.extend(more_origins.iter().cloned());
+                traced.push((
+                    Production {
+                        non_terminal: into.to_string(),
+                        derivation,
+                        span: production.span,
+                    },
+                    merged,
+                ));
+            }
+        }
+
+        self.rewrite(traced)
+    }
+}
+
+impl Ll1ification {
+    /// Whether the final grammar is LL(1)
+    pub fn is_ll1(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl fmt::Display for PipelineTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineTransform::RemoveUselessSymbols => write!(f, "Remove useless symbols"),
+            PipelineTransform::EliminateLeftRecursion => write!(f, "Eliminate left recursion"),
+            PipelineTransform::LeftFactor => write!(f, "Left factor"),
+            PipelineTransform::Substitute { symbol, into } => {
+                write!(f, "Substitute {} into {} and left factor", symbol, into)
+            }
+        }
+    }
+}
+
+/// Displays the step-by-step log followed by the verdict
+impl fmt::Display for Ll1ification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, step.transform)?;
+            for note in &step.notes {
+                writeln!(f, "   - {}", note)?;
+            }
+            writeln!(f, "   {} conflict(s) left", step.conflicts.len())?;
+        }
+
+        if self.is_ll1() {
+            write!(f, "✅ Grammar is LL(1)")
+        } else {
+            write!(
+                f,
+                "❌ {} conflict(s) cannot be removed by these transforms:",
+                self.conflicts.len()
+            )?;
+            for conflict in &self.conflicts {
+                write!(f, "\n   {}", conflict)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    #[test]
+    fn stops_when_left_recursion_cannot_be_removed() {
+        let grammar = Grammar::from_string("S -> a S b | S S | ε\n", "S").unwrap();
+        let result = grammar.ll1ify();
+        assert!(!result.is_ll1());
+        assert!(result.rewrite.grammar.productions.len() < 20);
+    }
+
+    #[test]
+    fn removes_left_recursion() {
+        let grammar = Grammar::from_string("E -> E '+' T | T\nT -> id\n", "E").unwrap();
+        assert!(grammar.ll1ify().is_ll1());
+    }
+}
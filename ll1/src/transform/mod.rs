@@ -0,0 +1,208 @@
+pub mod epsilon;
+pub mod factoring;
+pub mod inline;
+pub mod left_recursion;
+pub mod ll1ify;
+pub mod normal_form;
+pub mod original_tree;
+pub mod unit;
+pub mod useless;
+
+use indexmap::{IndexMap, IndexSet};
+
+use super::grammar::Production;
+use super::Grammar;
+
+/// A grammar rewritten by a transform, with the productions of the original
+/// grammar that each of its productions was derived from
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    pub grammar: Grammar,
+    /// Original productions behind each production of `grammar`, in the
+    /// order of `grammar.productions`; an unchanged production maps to itself
+    pub origins: IndexMap<Production, Vec<Production>>,
+}
+
+impl Rewrite {
+    /// A rewrite that leaves `grammar` as it is
+    pub fn unchanged(grammar: &Grammar) -> Rewrite {
+        grammar.rewrite(grammar.traced_rules().into_values().flatten().collect())
+    }
+
+    /// Applies `next`, a rewrite of this rewrite's grammar, tracing its
+    /// productions back to the productions this rewrite started from
+    pub fn then(&self, next: Rewrite) -> Rewrite {
+        let origins = next
+            .origins
+            .into_iter()
+            .map(|(production, from)| {
+                let mut traced: Vec<Production> = Vec::new();
+                for step in &from {
+                    let earlier = self
+                        .origins
+                        .get(step)
+                        .map_or(std::slice::from_ref(step), |o| o);
+                    for origin in earlier {
+                        if !traced.contains(origin) {
+                            traced.push(origin.clone());
+                        }
+                    }
+                }
+                (production, traced)
+            })
+            .collect();
+        Rewrite {
+            grammar: next.grammar,
+            origins,
+        }
+    }
+}
+
+/// A production together with the original productions it was derived from
+type Traced = (Production, Vec<Production>);
+
+impl Grammar {
+    /// Builds a grammar with the same start symbol, name and symbol kinds
+    /// as this one but holding only `productions`.
+    ///
+    /// Symbols keep the kind they have here, so terminals that look like
+    /// non-terminals (quoted literals, `%token`s) stay terminals.
+    pub(crate) fn with_productions(&self, productions: Vec<Production>) -> Grammar {
+        let mut grammar = Grammar::new(&self.start_symbol);
+        grammar.name = self.name.clone();
+        grammar.dialect = self.dialect;
+        grammar.symbol_mode = self.symbol_mode;
+        grammar.order = self.order;
+
+        for production in productions {
+            grammar
+                .non_terminals
+                .insert(production.non_terminal.clone());
+            for symbol in &production.derivation {
+                if self.terminals.contains(symbol) {
+                    grammar.terminals.insert(symbol.clone());
+                } else if symbol != "ε" {
+                    grammar.non_terminals.insert(symbol.clone());
+                }
+            }
+            grammar.productions.push(production);
+        }
+
+        grammar.helpers = self
+            .helpers
+            .iter()
+            .filter(|(helper, _)| grammar.non_terminals.contains(*helper))
+            .map(|(helper, origin)| (helper.clone(), origin.clone()))
+            .collect();
+        grammar
+    }
+
+    /// A name for a new non-terminal derived from `base`: `base'`, then
+    /// `base''` and so on, skipping symbols of the grammar and names in `taken`
+    pub(crate) fn fresh_non_terminal(&self, base: &str, taken: &IndexSet<String>) -> String {
+        let mut name = format!("{}'", base);
+        while self.non_terminals.contains(&name)
+            || self.terminals.contains(&name)
+            || taken.contains(&name)
+        {
+            name.push('\'');
+        }
+        name
+    }
+
+    /// The productions of each non-terminal, each traced back to itself
+    fn traced_rules(&self) -> IndexMap<String, Vec<Traced>> {
+        let mut rules: IndexMap<String, Vec<Traced>> = IndexMap::new();
+        for production in &self.productions {
+            rules
+                .entry(production.non_terminal.clone())
+                .or_default()
+                .push((production.clone(), vec![production.clone()]));
+        }
+        rules
+    }
+
+    /// Builds the rewritten grammar, merging the origins of productions
+    /// that came out the same
+    fn rewrite(&self, traced: Vec<Traced>) -> Rewrite {
+        let mut origins: IndexMap<Production, Vec<Production>> = IndexMap::new();
+        for (production, from) in traced {
+            let entry = origins.entry(production).or_default();
+            for origin in from {
+                if !entry.contains(&origin) {
+                    entry.push(origin);
+                }
+            }
+        }
+        Rewrite {
+            grammar: self.with_productions(origins.keys().cloned().collect()),
+            origins,
+        }
+    }
+
+    /// One cycle of `graph` for each group of symbols that reach each other,
+    /// the shortest through the group's earliest symbol in `ordering`
+    fn cycles_in(
+        graph: &IndexMap<String, IndexSet<String>>,
+        ordering: &IndexSet<String>,
+    ) -> Vec<Vec<String>> {
+        let mut cycles: Vec<Vec<String>> = Vec::new();
+        for symbol in ordering {
+            let covered = cycles.iter().flatten().any(|member| {
+                Self::reaches(graph, member, symbol) && Self::reaches(graph, symbol, member)
+            });
+            if covered {
+                continue;
+            }
+            if let Some(cycle) = Self::shortest_cycle(graph, symbol) {
+                cycles.push(cycle);
+            }
+        }
+        cycles
+    }
+
+    /// Whether there is a path of one or more edges from `from` to `to`
+    fn reaches(graph: &IndexMap<String, IndexSet<String>>, from: &str, to: &str) -> bool {
+        let mut seen = IndexSet::new();
+        let mut stack = vec![from];
+        while let Some(symbol) = stack.pop() {
+            for next in graph.get(symbol).into_iter().flatten() {
+                if next == to {
+                    return true;
+                }
+                if seen.insert(next.as_str()) {
+                    stack.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// The shortest path from `start` back to itself
+    fn shortest_cycle(
+        graph: &IndexMap<String, IndexSet<String>>,
+        start: &str,
+    ) -> Option<Vec<String>> {
+        let mut previous: IndexMap<&str, &str> = IndexMap::new();
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(symbol) = queue.pop_front() {
+            for next in graph.get(symbol).into_iter().flatten() {
+                if next == start {
+                    let mut cycle = vec![symbol.to_string()];
+                    let mut current = symbol;
+                    while current != start {
+                        current = previous[current];
+                        cycle.push(current.to_string());
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if !previous.contains_key(next.as_str()) {
+                    previous.insert(next, symbol);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
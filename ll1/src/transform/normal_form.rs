@@ -0,0 +1,432 @@
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+
+use super::{Rewrite, Traced};
+use crate::grammar::Production;
+use crate::Grammar;
+
+/// A step of the conversion to Chomsky or Greibach normal form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NormalFormStep {
+    /// Adds a start symbol `S' → S` that no derivation uses
+    Start,
+    /// Moves each terminal of a longer derivation into its own rule `T_a → a`
+    Term,
+    /// Splits derivations of more than two symbols into chains of pairs
+    Bin,
+    /// Removes the ε-productions, keeping `S' → ε` when ε is in the language
+    Del,
+    /// Removes the unit productions `A → B`
+    Unit,
+    /// Greibach only: removes left recursion, with tails that do not derive ε
+    LeftRecursion,
+    /// Greibach only: substitutes leading non-terminals until every derivation
+    /// starts with a terminal
+    Substitute,
+}
+
+/// The grammar after one step, traced back to the original grammar
+#[derive(Debug, Clone)]
+pub struct NormalFormStage {
+    pub step: NormalFormStep,
+    pub rewrite: Rewrite,
+}
+
+/// The result of `Grammar::to_cnf` or `Grammar::to_gnf`
+#[derive(Debug, Clone)]
+pub struct NormalForm {
+    /// Every intermediate grammar, in the order the steps were applied
+    pub stages: Vec<NormalFormStage>,
+}
+
+impl Grammar {
+    /// Converts the grammar to Chomsky normal form, where every production
+    /// is `A → B C`, `A → a` or `S' → ε`, applying START, TERM, BIN, DEL and
+    /// UNIT in that order.
+    ///
+    /// Fresh non-terminals are named deterministically: `S'` for the new start
+    /// symbol, `T_a` for the terminal `a` (`T_1`, `T_2`, … by terminal order
+    /// for terminals that are not words) and `A1`, `A2`, … for the pieces of
+    /// `A`'s long derivations, with primes added when a name is taken. The
+    /// UNIT step also drops the rules that are no longer reachable.
+    pub fn to_cnf(&self) -> NormalForm {
+        let mut stages: Vec<NormalFormStage> = Vec::new();
+        let mut current = Rewrite::unchanged(self);
+
+        let steps = [
+            NormalFormStep::Start,
+            NormalFormStep::Term,
+            NormalFormStep::Bin,
+            NormalFormStep::Del,
+            NormalFormStep::Unit,
+        ];
+        for step in steps {
+            let grammar = &current.grammar;
+            let next = match step {
+                NormalFormStep::Start => grammar.cnf_start(),
+                NormalFormStep::Term => grammar.cnf_term(),
+                NormalFormStep::Bin => grammar.cnf_bin(),
+                NormalFormStep::Del => grammar.remove_epsilon_productions(false),
+                _ => {
+                    // After DEL only `S'` derives ε, and START made sure no
+                    // derivation uses it, so every cycle `A ⇒+ A` goes through
+                    // unit productions alone and UNIT removes it
+                    let rewrite = grammar
+                        .eliminate_unit_productions()
+                        .expect("no cycle survives UNIT once DEL has run");
+                    rewrite.then(rewrite.grammar.useful_part())
+                }
+            };
+            current = current.then(next);
+            stages.push(NormalFormStage {
+                step,
+                rewrite: current.clone(),
+            });
+        }
+
+        NormalForm { stages }
+    }
+
+    /// Converts the grammar to Greibach normal form, where every production
+    /// is `A → a B₁ … Bₙ` or `S' → ε`.
+    ///
+    /// Starts from Chomsky normal form, removes left recursion with Paull's
+    /// algorithm in grammar order, turning the tails `A' → α A' | ε` into
+    /// `A' → α A' | α`, then substitutes leading non-terminals until every
+    /// derivation starts with a terminal, dropping the rules no longer reachable.
+    pub fn to_gnf(&self) -> NormalForm {
+        let mut normal_form = self.to_cnf();
+        let mut current = normal_form.rewrite().clone();
+
+        let removal = current.grammar.eliminate_left_recursion();
+        let without_tails_epsilon = removal.rewrite.grammar.remove_epsilon_productions(false);
+        current = current.then(removal.rewrite).then(without_tails_epsilon);
+        normal_form.stages.push(NormalFormStage {
+            step: NormalFormStep::LeftRecursion,
+            rewrite: current.clone(),
+        });
+
+        current = current.then(current.grammar.substitute_leading_non_terminals());
+        current = current.then(current.grammar.useful_part());
+        normal_form.stages.push(NormalFormStage {
+            step: NormalFormStep::Substitute,
+            rewrite: current,
+        });
+        normal_form
+    }
+
+    /// START: `S' → S`
+    fn cnf_start(&self) -> Rewrite {
+        let start = self.fresh_non_terminal(&self.start_symbol, &IndexSet::new());
+        let mut grammar = self.clone();
+        grammar.start_symbol = start.clone();
+
+        let production = Production {
+            non_terminal: start,
+            derivation: vec![self.start_symbol.clone()],
+            span: None,
+        };
+        let start_origins = self
+            .productions
+            .iter()
+            .filter(|p| p.non_terminal == self.start_symbol)
+            .cloned()
+            .collect();
+        let mut traced: Vec<Traced> = vec![(production, start_origins)];
+        traced.extend(self.traced_rules().into_values().flatten());
+        grammar.rewrite(traced)
+    }
+
+    /// TERM: `A → a B` becomes `A → T_a B` with `T_a → a`
+    fn cnf_term(&self) -> Rewrite {
+        let mut taken = IndexSet::new();
+        let mut names: IndexMap<&String, String> = IndexMap::new();
+        let mut traced: Vec<Traced> = Vec::new();
+        let mut term_rules: Vec<Traced> = Vec::new();
+
+        for (production, origins) in self.traced_rules().into_values().flatten() {
+            if production.derivation.len() < 2 {
+                traced.push((production, origins));
+                continue;
+            }
+            let derivation = production
+                .derivation
+                .iter()
+                .map(|symbol| {
+                    let Some(terminal) = self.terminals.get(symbol) else {
+                        return symbol.clone();
+                    };
+                    if let Some(name) = names.get(terminal) {
+                        return name.clone();
+                    }
+                    let name = if terminal.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        format!("T_{}", terminal)
+                    } else {
+                        format!(
+                            "T_{}",
+                            self.terminals.get_index_of(terminal).unwrap_or(0) + 1
+                        )
+                    };
+                    let name = self.unused_name(name, &taken);
+                    taken.insert(name.clone());
+                    names.insert(terminal, name.clone());
+                    term_rules.push((
+                        Production {
+                            non_terminal: name.clone(),
+                            derivation: vec![terminal.clone()],
+                            span: None,
+                        },
+                        Vec::new(),
+                    ));
+                    name
+                })
+                .collect();
+            let replaced = Production {
+                non_terminal: production.non_terminal.clone(),
+                derivation,
+                span: production.span,
+            };
+            for (term_rule, term_origins) in term_rules.iter_mut() {
+                if replaced.derivation.contains(&term_rule.non_terminal) {
+                    term_origins.push(production.clone());
+                }
+            }
+            traced.push((replaced, origins));
+        }
+
+        traced.extend(term_rules);
+        self.rewrite(traced)
+    }
+
+    /// BIN: `A → B C D` becomes `A → B A1` with `A1 → C D`
+    fn cnf_bin(&self) -> Rewrite {
+        let mut taken = IndexSet::new();
+        let mut counters: IndexMap<String, usize> = IndexMap::new();
+        let mut traced: Vec<Traced> = Vec::new();
+
+        for (production, origins) in self.traced_rules().into_values().flatten() {
+            if production.derivation.len() <= 2 {
+                traced.push((production, origins));
+                continue;
+            }
+
+            let mut head = production.non_terminal.clone();
+            let symbols = &production.derivation;
+            for (i, symbol) in symbols[..symbols.len() - 2].iter().enumerate() {
+                let counter = counters.entry(production.non_terminal.clone()).or_default();
+                *counter += 1;
+                let name = format!("{}{}", production.non_terminal, counter);
+                let name = self.unused_name(name, &taken);
+                taken.insert(name.clone());
+
+                let piece = Production {
+                    non_terminal: head,
+                    derivation: vec![symbol.clone(), name.clone()],
+                    span: if i == 0 { production.span } else { None },
+                };
+                traced.push((piece, origins.clone()));
+                head = name;
+            }
+            let last = Production {
+                non_terminal: head,
+                derivation: symbols[symbols.len() - 2..].to_vec(),
+                span: None,
+            };
+            traced.push((last, origins));
+        }
+
+        self.rewrite(traced)
+    }
+
+    /// Replaces a leading non-terminal by its alternatives until every
+    /// derivation starts with a terminal or the grammar stops changing
+    fn substitute_leading_non_terminals(&self) -> Rewrite {
+        let mut rules = self.traced_rules();
+        let leads_with_non_terminal = |production: &Production| {
+            production
+                .derivation
+                .first()
+                .is_some_and(|symbol| self.non_terminals.contains(symbol))
+        };
+
+        loop {
+            let mut changed = false;
+            let snapshot = rules.clone();
+            for alternatives in rules.values_mut() {
+                let mut substituted = Vec::new();
+                for (production, origins) in alternatives.drain(..) {
+                    let lead = production.derivation[0].clone();
+                    let resolved = snapshot.get(&lead).filter(|lead_rules| {
+                        lead_rules
+                            .iter()
+                            .all(|(lead_production, _)| !leads_with_non_terminal(lead_production))
+                    });
+                    let Some(lead_rules) =
+                        resolved.filter(|_| leads_with_non_terminal(&production))
+                    else {
+                        substituted.push((production, origins));
+                        continue;
+                    };
+                    for (lead_production, lead_origins) in lead_rules {
+                        let mut derivation = lead_production.derivation.clone();
+                        derivation.extend(production.derivation[1..].iter().cloned());
+                        let mut merged = origins.clone();
+                        merged.extend(lead_origins.iter().cloned());
+                        substituted.push((
+                            Production {
+                                non_terminal: production.non_terminal.clone(),
+                                derivation,
+                                span: production.span,
+                            },
+                            merged,
+                        ));
+                    }
+                    changed = true;
+                }
+                *alternatives = substituted;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.rewrite(rules.into_values().flatten().collect())
+    }
+
+    /// The grammar without its useless symbols, as a rewrite
+    fn useful_part(&self) -> Rewrite {
+        Rewrite::unchanged(&self.remove_useless_symbols().grammar)
+    }
+
+    /// `name` itself when the grammar and `taken` do not use it, or a fresh
+    /// name with primes otherwise
+    fn unused_name(&self, name: String, taken: &IndexSet<String>) -> String {
+        if self.non_terminals.contains(&name)
+            || self.terminals.contains(&name)
+            || taken.contains(&name)
+        {
+            self.fresh_non_terminal(&name, taken)
+        } else {
+            name
+        }
+    }
+}
+
+impl NormalForm {
+    /// The grammar after the last step
+    pub fn rewrite(&self) -> &Rewrite {
+        &self.stages.last().expect("a conversion has steps").rewrite
+    }
+
+    /// The grammar right after `step`, if the conversion applied it
+    pub fn stage(&self, step: NormalFormStep) -> Option<&Rewrite> {
+        self.stages
+            .iter()
+            .rev()
+            .find(|stage| stage.step == step)
+            .map(|stage| &stage.rewrite)
+    }
+}
+
+impl fmt::Display for NormalFormStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NormalFormStep::Start => write!(f, "START"),
+            NormalFormStep::Term => write!(f, "TERM"),
+            NormalFormStep::Bin => write!(f, "BIN"),
+            NormalFormStep::Del => write!(f, "DEL"),
+            NormalFormStep::Unit => write!(f, "UNIT"),
+            NormalFormStep::LeftRecursion => write!(f, "LEFT RECURSION"),
+            NormalFormStep::Substitute => write!(f, "SUBSTITUTE"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::Grammar;
+
+    const SOURCE: &str = "S -> a S b | A\nA -> c A | ε\n";
+
+    /// The sentences of at most `max_len` terminals, found by expanding the
+    /// leftmost non-terminal of forms no longer than `max_len + 2` symbols
+    fn sentences(grammar: &Grammar, max_len: usize) -> BTreeSet<Vec<String>> {
+        let mut sentences = BTreeSet::new();
+        let mut seen = BTreeSet::new();
+        let mut forms = vec![vec![grammar.start_symbol.clone()]];
+        while let Some(form) = forms.pop() {
+            let Some(i) = form.iter().position(|s| grammar.non_terminals.contains(s)) else {
+                if form.len() <= max_len {
+                    sentences.insert(form);
+                }
+                continue;
+            };
+            for production in &grammar.productions {
+                if production.non_terminal != form[i] {
+                    continue;
+                }
+                let mut next = form[..i].to_vec();
+                next.extend(production.derivation.iter().filter(|s| *s != "ε").cloned());
+                next.extend(form[i + 1..].iter().cloned());
+                let terminals = next
+                    .iter()
+                    .filter(|s| grammar.terminals.contains(*s))
+                    .count();
+                if terminals <= max_len && next.len() <= max_len + 2 && seen.insert(next.clone()) {
+                    forms.push(next);
+                }
+            }
+        }
+        sentences
+    }
+
+    #[test]
+    fn cnf_rules_have_chomsky_shape() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let cnf = grammar.to_cnf().rewrite().grammar.clone();
+        for production in &cnf.productions {
+            let derivation = &production.derivation;
+            let shaped = match derivation.as_slice() {
+                [symbol] if symbol == "ε" => production.non_terminal == cnf.start_symbol,
+                [symbol] => cnf.terminals.contains(symbol),
+                [first, second] => {
+                    cnf.non_terminals.contains(first) && cnf.non_terminals.contains(second)
+                }
+                _ => false,
+            };
+            assert!(shaped, "{:?} is not in CNF", production);
+            assert!(!derivation.contains(&cnf.start_symbol));
+        }
+    }
+
+    #[test]
+    fn gnf_rules_have_greibach_shape() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let gnf = grammar.to_gnf().rewrite().grammar.clone();
+        for production in &gnf.productions {
+            let derivation = &production.derivation;
+            let shaped = if derivation == &["ε"] {
+                production.non_terminal == gnf.start_symbol
+            } else {
+                gnf.terminals.contains(&derivation[0])
+                    && derivation[1..]
+                        .iter()
+                        .all(|symbol| gnf.non_terminals.contains(symbol))
+            };
+            assert!(shaped, "{:?} is not in GNF", production);
+        }
+    }
+
+    #[test]
+    fn normal_forms_keep_the_language() {
+        let grammar = Grammar::from_string(SOURCE, "S").unwrap();
+        let expected = sentences(&grammar, 4);
+        assert!(expected.contains(&vec!["a".to_string(), "c".to_string(), "b".to_string()]));
+        assert!(expected.contains(&Vec::new()));
+        assert_eq!(sentences(&grammar.to_cnf().rewrite().grammar, 4), expected);
+        assert_eq!(sentences(&grammar.to_gnf().rewrite().grammar, 4), expected);
+    }
+}
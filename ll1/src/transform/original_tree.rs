@@ -0,0 +1,230 @@
+use indexmap::{IndexMap, IndexSet};
+
+use super::Rewrite;
+use crate::grammar::Production;
+use crate::tree::ParseTree;
+use crate::Grammar;
+
+/// Rebuilds the children of one node of the original grammar from the
+/// subtrees and terminals found below the matching node of the rewritten tree
+struct Regrouping<'a> {
+    /// Already rebuilt subtrees and leaves, from left to right
+    items: &'a [ParseTree],
+    rules: IndexMap<&'a str, Vec<&'a Production>>,
+    non_terminals: &'a IndexSet<String>,
+    memo: IndexMap<(String, usize, usize), Option<ParseTree>>,
+    /// Symbols and spans being matched, to cut left-recursive loops
+    active: IndexSet<(String, usize, usize)>,
+    /// How many times a loop was cut; a failure found after a cut may only
+    /// hold for the node being matched, so it is not remembered
+    cuts: usize,
+}
+
+impl Rewrite {
+    /// Turns `tree`, built with a parser for this rewrite's grammar, into the
+    /// tree `original` gives the same input, `original` being the grammar
+    /// the rewrite started from.
+    ///
+    /// Nodes of non-terminals that `original` does not have, such as the
+    /// tails `E'` added by left-recursion removal, are spliced into their
+    /// parent. The symbols under each node of an original non-terminal are
+    /// then regrouped with the original productions they were derived from,
+    /// as recorded in `origins`, or with every production of `original` if
+    /// those are not enough. Non-terminals removed by a transform get their
+    /// nodes back. When a sequence can be grouped in more than one way, the
+    /// leftmost subtree is made as long as possible, so `1 - 2 - 3` under
+    /// `E → E - T | T` groups as `(1 - 2) - 3` again.
+    ///
+    /// Fails when the symbols under a node cannot be derived from it in
+    /// `original`, which means `tree` was not built with this rewrite.
+    pub fn original_tree(&self, original: &Grammar, tree: &ParseTree) -> Result<ParseTree, String> {
+        let ParseTree::Node { production, .. } = tree else {
+            return Ok(tree.clone());
+        };
+
+        let mut items: Vec<ParseTree> = Vec::new();
+        let mut used: IndexSet<&Production> = IndexSet::new();
+        self.flatten(original, tree, &mut items, &mut used)?;
+
+        let symbol = if original.non_terminals.contains(&production.non_terminal) {
+            production.non_terminal.as_str()
+        } else {
+            original.start_symbol.as_str()
+        };
+        let recorded = used
+            .iter()
+            .flat_map(|p| self.origins.get(*p).into_iter().flatten())
+            .collect::<Vec<_>>();
+        let everything = original.productions.iter().collect::<Vec<_>>();
+
+        for productions in [recorded, everything] {
+            let mut regrouping = Regrouping::new(&items, productions, &original.non_terminals);
+            if let Some(node) = regrouping.derive(symbol, 0, items.len()) {
+                return Ok(node);
+            }
+        }
+        Err(format!(
+            "{} does not derive '{}' in the original grammar",
+            symbol,
+            items
+                .iter()
+                .map(ParseTree::symbol)
+                .collect::<Vec<_>>()
+                .join(" ")
+        ))
+    }
+
+    /// Collects the children of `node`, descending into the nodes of
+    /// non-terminals that `original` does not have and rebuilding the others
+    fn flatten<'a>(
+        &self,
+        original: &Grammar,
+        node: &'a ParseTree,
+        items: &mut Vec<ParseTree>,
+        used: &mut IndexSet<&'a Production>,
+    ) -> Result<(), String> {
+        let ParseTree::Node {
+            production,
+            children,
+        } = node
+        else {
+            return Ok(());
+        };
+        used.insert(production);
+
+        for child in children {
+            match child {
+                ParseTree::Leaf(_) => items.push(child.clone()),
+                ParseTree::Node { production, .. }
+                    if original.non_terminals.contains(&production.non_terminal) =>
+                {
+                    items.push(self.original_tree(original, child)?)
+                }
+                ParseTree::Node { .. } => self.flatten(original, child, items, used)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Regrouping<'a> {
+    fn new(
+        items: &'a [ParseTree],
+        productions: Vec<&'a Production>,
+        non_terminals: &'a IndexSet<String>,
+    ) -> Self {
+        let mut rules: IndexMap<&str, Vec<&Production>> = IndexMap::new();
+        for production in productions {
+            let alternatives = rules.entry(&production.non_terminal).or_default();
+            if !alternatives.contains(&production) {
+                alternatives.push(production);
+            }
+        }
+        Regrouping {
+            items,
+            rules,
+            non_terminals,
+            memo: IndexMap::new(),
+            active: IndexSet::new(),
+            cuts: 0,
+        }
+    }
+
+    /// A tree for `symbol` whose leaves are `items[start..end]`, taking an
+    /// item as it is when it is a single `symbol`
+    fn derive(&mut self, symbol: &str, start: usize, end: usize) -> Option<ParseTree> {
+        if end == start + 1 && self.items[start].symbol() == symbol {
+            return Some(self.items[start].clone());
+        }
+        if !self.non_terminals.contains(symbol) {
+            return None;
+        }
+
+        let key = (symbol.to_string(), start, end);
+        if let Some(tree) = self.memo.get(&key) {
+            return tree.clone();
+        }
+        if !self.active.insert(key.clone()) {
+            self.cuts += 1;
+            return None;
+        }
+        let cuts = self.cuts;
+
+        let alternatives = self.rules.get(symbol).cloned().unwrap_or_default();
+        let tree = alternatives.into_iter().find_map(|production| {
+            let symbols: Vec<&str> = production
+                .derivation
+                .iter()
+                .map(String::as_str)
+                .filter(|s| *s != "ε")
+                .collect();
+            self.sequence(&symbols, start, end)
+                .map(|children| ParseTree::Node {
+                    production: production.clone(),
+                    children,
+                })
+        });
+
+        self.active.shift_remove(&key);
+        if tree.is_some() || self.cuts == cuts {
+            self.memo.insert(key, tree.clone());
+        }
+        tree
+    }
+
+    /// Trees for `symbols` covering `items[start..end]`, trying the longest
+    /// first tree first
+    fn sequence(&mut self, symbols: &[&str], start: usize, end: usize) -> Option<Vec<ParseTree>> {
+        let Some((first, rest)) = symbols.split_first() else {
+            return (start == end).then(Vec::new);
+        };
+        for middle in (start..=end).rev() {
+            let Some(tree) = self.derive(first, start, middle) else {
+                continue;
+            };
+            if let Some(mut trees) = self.sequence(rest, middle, end) {
+                trees.insert(0, tree);
+                return Some(trees);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::ParseTree;
+    use crate::{Grammar, Parser};
+
+    /// The leaves of `tree`, with each node of more than one child in brackets
+    fn grouping(tree: &ParseTree) -> String {
+        match tree {
+            ParseTree::Leaf(terminal) => terminal.clone(),
+            ParseTree::Node { children, .. } => {
+                let parts: Vec<String> = children.iter().map(grouping).collect();
+                match parts.len() {
+                    1 => parts[0].clone(),
+                    _ => format!("({})", parts.join(" ")),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn restores_left_associativity_after_left_recursion_removal() {
+        let grammar =
+            Grammar::from_string("E -> E '-' T | T\nT -> '1' | '2' | '3'\n", "E").unwrap();
+        let rewrite = grammar.eliminate_left_recursion().rewrite;
+        let mut parser = Parser::new(rewrite.grammar.clone()).unwrap();
+        parser.set_input("1 - 2 - 3".to_string());
+        let tree = parser.parse_tree().unwrap();
+        assert_ne!(grouping(&tree), "((1 - 2) - 3)");
+
+        let original = rewrite.original_tree(&grammar, &tree).unwrap();
+        assert_eq!(grouping(&original), "((1 - 2) - 3)");
+        let ParseTree::Node { production, .. } = &original else {
+            panic!("the root is a node");
+        };
+        assert_eq!(production.derivation, ["E", "-", "T"]);
+    }
+}
@@ -0,0 +1,102 @@
+use indexmap::{IndexMap, IndexSet};
+
+use super::{Rewrite, Traced};
+use crate::error::GrammarError;
+use crate::grammar::Production;
+use crate::Grammar;
+
+impl Grammar {
+    /// Whether the production is `A → B` for a non-terminal `B`
+    pub fn is_unit_production(&self, production: &Production) -> bool {
+        production.derivation.len() == 1 && self.non_terminals.contains(&production.derivation[0])
+    }
+
+    /// Derivations `A ⇒+ A`, one for each group of non-terminals that derive
+    /// each other, as the non-terminals along it: `[A, B]` is `A ⇒+ B ⇒+ A`.
+    ///
+    /// Besides unit productions, a step may go through symbols that derive ε,
+    /// as in `A → A B` with `B → ε`. A cyclic grammar is ambiguous, since
+    /// such a cycle can be repeated any number of times.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let nullable = self.nullable_non_terminals();
+        let mut graph: IndexMap<String, IndexSet<String>> = IndexMap::new();
+        for production in &self.productions {
+            let symbols: Vec<&String> = production
+                .derivation
+                .iter()
+                .filter(|symbol| *symbol != "ε")
+                .collect();
+            let entry = graph.entry(production.non_terminal.clone()).or_default();
+            for (i, symbol) in symbols.iter().enumerate() {
+                let others_nullable = symbols
+                    .iter()
+                    .enumerate()
+                    .all(|(j, other)| i == j || nullable.contains(*other));
+                if others_nullable && self.non_terminals.contains(*symbol) {
+                    entry.insert((*symbol).clone());
+                }
+            }
+        }
+        Self::cycles_in(&graph, &self.non_terminals)
+    }
+
+    /// Removes every unit production `A → B`.
+    ///
+    /// Each non-terminal takes over the other productions of the non-terminals
+    /// it reaches through unit productions, so unit cycles such as `A → B`,
+    /// `B → A` disappear as well. Each new production is traced to the unit
+    /// productions it skips and the production it copies.
+    ///
+    /// Fails with `GrammarError::CyclicGrammar` when the result is still
+    /// cyclic, which happens when a cycle goes through symbols that derive ε;
+    /// removing the ε-productions first breaks those cycles.
+    pub fn eliminate_unit_productions(&self) -> Result<Rewrite, GrammarError> {
+        let rules = self.traced_rules();
+        let mut traced: Vec<Traced> = Vec::new();
+
+        for non_terminal in rules.keys() {
+            // Non-terminals reachable through unit productions, with the unit
+            // productions leading to each of them
+            let mut chains: IndexMap<&String, Vec<Production>> =
+                IndexMap::from([(non_terminal, Vec::new())]);
+            let mut index = 0;
+            while let Some((&current, chain)) = chains.get_index(index) {
+                let chain = chain.clone();
+                for (production, _) in rules.get(current).into_iter().flatten() {
+                    if !self.is_unit_production(production) {
+                        continue;
+                    }
+                    let next = &production.derivation[0];
+                    if !chains.contains_key(next) {
+                        let mut longer = chain.clone();
+                        longer.push(production.clone());
+                        chains.insert(next, longer);
+                    }
+                }
+                index += 1;
+            }
+
+            for (reached, chain) in chains {
+                for (production, origins) in rules.get(reached).into_iter().flatten() {
+                    if self.is_unit_production(production) {
+                        continue;
+                    }
+                    let mut from = chain.clone();
+                    from.extend(origins.iter().cloned());
+                    let copy = Production {
+                        non_terminal: non_terminal.clone(),
+                        derivation: production.derivation.clone(),
+                        span: production.span,
+                    };
+                    traced.push((copy, from));
+                }
+            }
+        }
+
+        let rewrite = self.rewrite(traced);
+        match rewrite.grammar.cycles().into_iter().next() {
+            Some(cycle) => Err(GrammarError::CyclicGrammar { cycle }),
+            None => Ok(rewrite),
+        }
+    }
+}
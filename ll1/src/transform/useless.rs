@@ -0,0 +1,200 @@
+use std::fmt;
+
+use indexmap::IndexSet;
+
+use crate::grammar::Production;
+use crate::Grammar;
+
+/// Why a symbol was removed from a grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Uselessness {
+    /// The non-terminal derives no string of terminals
+    Unproductive,
+    /// The symbol cannot be reached from the start symbol
+    Unreachable,
+}
+
+/// A symbol removed by `Grammar::remove_useless_symbols`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedSymbol {
+    pub symbol: String,
+    pub reason: Uselessness,
+}
+
+/// The reduced grammar together with what was taken out of it
+#[derive(Debug, Clone)]
+pub struct Reduction {
+    pub grammar: Grammar,
+    /// Unproductive symbols first, then unreachable ones, each in grammar order
+    pub removed_symbols: Vec<RemovedSymbol>,
+    pub removed_productions: Vec<Production>,
+}
+
+impl Grammar {
+    /// Non-terminals that derive no string of terminals.
+    ///
+    /// A non-terminal is productive when one of its productions holds only
+    /// terminals, ε and productive non-terminals; the rest are unproductive.
+    pub fn unproductive_non_terminals(&self) -> IndexSet<String> {
+        let mut productive: IndexSet<&String> = IndexSet::new();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                if productive.contains(&production.non_terminal) {
+                    continue;
+                }
+                let derives_terminals = production.derivation.iter().all(|symbol| {
+                    symbol == "ε" || self.terminals.contains(symbol) || productive.contains(symbol)
+                });
+                if derives_terminals {
+                    productive.insert(&production.non_terminal);
+                    changed = true;
+                }
+            }
+        }
+
+        self.non_terminals
+            .iter()
+            .filter(|nt| !productive.contains(nt))
+            .cloned()
+            .collect()
+    }
+
+    /// Non-terminals that no sentential form derived from the start symbol contains
+    pub fn unreachable_non_terminals(&self) -> IndexSet<String> {
+        let reachable = self.reachable_symbols();
+        self.non_terminals
+            .iter()
+            .filter(|nt| !reachable.contains(*nt))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes unproductive non-terminals, then the symbols that became unreachable.
+    ///
+    /// Unproductive symbols go first: dropping the productions that use them
+    /// can leave further symbols unreachable, while the reverse never happens.
+    /// When the start symbol itself is unproductive the grammar generates no
+    /// string and the reduced grammar keeps the start symbol with no productions.
+    pub fn remove_useless_symbols(&self) -> Reduction {
+        let mut removed_symbols = Vec::new();
+        let mut removed_productions = Vec::new();
+
+        let unproductive = self.unproductive_non_terminals();
+        let (kept, dropped): (Vec<Production>, Vec<Production>) =
+            self.productions.iter().cloned().partition(|production| {
+                !unproductive.contains(&production.non_terminal)
+                    && !production
+                        .derivation
+                        .iter()
+                        .any(|symbol| unproductive.contains(symbol))
+            });
+        removed_productions.extend(dropped);
+        removed_symbols.extend(Self::removed(unproductive, Uselessness::Unproductive));
+
+        let productive = self.with_productions(kept);
+        let reachable = productive.reachable_symbols();
+        let (kept, dropped): (Vec<Production>, Vec<Production>) = productive
+            .productions
+            .iter()
+            .cloned()
+            .partition(|production| reachable.contains(&production.non_terminal));
+        removed_productions.extend(dropped);
+
+        let unreachable = productive
+            .non_terminals
+            .iter()
+            .chain(&self.terminals)
+            .filter(|symbol| !reachable.contains(*symbol))
+            .cloned()
+            .collect();
+        removed_symbols.extend(Self::removed(unreachable, Uselessness::Unreachable));
+
+        Reduction {
+            grammar: self.with_productions(kept),
+            removed_symbols,
+            removed_productions,
+        }
+    }
+
+    /// Symbols, terminals included, that appear in some sentential form
+    /// derived from the start symbol
+    fn reachable_symbols(&self) -> IndexSet<String> {
+        let mut reachable = IndexSet::from([self.start_symbol.clone()]);
+        let mut stack = vec![self.start_symbol.clone()];
+
+        while let Some(symbol) = stack.pop() {
+            for production in &self.productions {
+                if production.non_terminal != symbol {
+                    continue;
+                }
+                for next in &production.derivation {
+                    if next != "ε" && reachable.insert(next.clone()) {
+                        stack.push(next.clone());
+                    }
+                }
+            }
+        }
+
+        reachable
+    }
+
+    fn removed(symbols: IndexSet<String>, reason: Uselessness) -> Vec<RemovedSymbol> {
+        symbols
+            .into_iter()
+            .map(|symbol| RemovedSymbol { symbol, reason })
+            .collect()
+    }
+}
+
+impl fmt::Display for RemovedSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Uselessness::Unproductive => {
+                write!(
+                    f,
+                    "{}: unproductive, derives no terminal string",
+                    self.symbol
+                )
+            }
+            Uselessness::Unreachable => {
+                write!(f, "{}: unreachable from the start symbol", self.symbol)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RemovedSymbol, Uselessness};
+    use crate::Grammar;
+
+    #[test]
+    fn removes_unproductive_and_unreachable_symbols() {
+        let grammar = Grammar::from_string("S -> a | B c\nB -> B b\nC -> d\n", "S").unwrap();
+        let reduction = grammar.remove_useless_symbols();
+        let removed = |symbol: &str, reason| RemovedSymbol {
+            symbol: symbol.to_string(),
+            reason,
+        };
+        assert_eq!(
+            reduction.removed_symbols,
+            [
+                removed("B", Uselessness::Unproductive),
+                removed("C", Uselessness::Unreachable),
+                removed("c", Uselessness::Unreachable),
+                removed("b", Uselessness::Unreachable),
+                removed("d", Uselessness::Unreachable),
+            ]
+        );
+        assert_eq!(reduction.removed_productions.len(), 3);
+
+        let reduced = &reduction.grammar;
+        assert_eq!(reduced.productions.len(), 1);
+        assert_eq!(reduced.productions[0].derivation, ["a"]);
+        assert_eq!(reduced.non_terminals.len(), 1);
+        assert_eq!(reduced.terminals.iter().collect::<Vec<_>>(), ["a"]);
+    }
+}
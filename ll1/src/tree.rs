@@ -0,0 +1,142 @@
+use std::fmt;
+
+use super::grammar::Production;
+
+/// A parse tree built by `Parser::parse_tree`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTree {
+    /// A terminal matched in the input
+    Leaf(String),
+    /// A non-terminal with the production applied to it and one child per
+    /// symbol of the derivation; an ε-production has no children
+    Node {
+        production: Production,
+        children: Vec<ParseTree>,
+    },
+}
+
+impl ParseTree {
+    /// The terminal of a leaf or the non-terminal of a node
+    pub fn symbol(&self) -> &str {
+        match self {
+            ParseTree::Leaf(terminal) => terminal,
+            ParseTree::Node { production, .. } => &production.non_terminal,
+        }
+    }
+
+    /// The terminals at the leaves, from left to right
+    pub fn terminals(&self) -> Vec<&str> {
+        match self {
+            ParseTree::Leaf(terminal) => vec![terminal],
+            ParseTree::Node { children, .. } => {
+                children.iter().flat_map(ParseTree::terminals).collect()
+            }
+        }
+    }
+
+    /// Replaces every node built from a unit production `A → B`, whose
+    /// derivation is a single non-terminal, by its only child, so a chain
+    /// `A → B → C` keeps just the `C` node. Nodes with other derivations are
+    /// kept even when only one child is left, as for `A → B C` with the ε
+    /// subtree of `C` pruned.
+    pub fn collapse_units(self) -> ParseTree {
+        match self {
+            ParseTree::Leaf(_) => self,
+            ParseTree::Node {
+                production,
+                mut children,
+            } => {
+                let is_unit = production.derivation.len() == 1
+                    && matches!(
+                        children.as_slice(),
+                        [ParseTree::Node { production: child, .. }]
+                            if child.non_terminal == production.derivation[0]
+                    );
+                if is_unit {
+                    return children.remove(0).collapse_units();
+                }
+                ParseTree::Node {
+                    production,
+                    children: children
+                        .into_iter()
+                        .map(ParseTree::collapse_units)
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, prefix: &str, last: bool) -> fmt::Result {
+        let branch = if last { "└── " } else { "├── " };
+        match self {
+            ParseTree::Leaf(terminal) => writeln!(f, "{}{}{}", prefix, branch, terminal),
+            ParseTree::Node {
+                production,
+                children,
+            } => {
+                writeln!(f, "{}{}{}", prefix, branch, production)?;
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                for (i, child) in children.iter().enumerate() {
+                    child.fmt_indented(f, &prefix, i + 1 == children.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Displays the tree one node per line, each node showing its production
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTree::Leaf(terminal) => writeln!(f, "{}", terminal),
+            ParseTree::Node {
+                production,
+                children,
+            } => {
+                writeln!(f, "{}", production)?;
+                for (i, child) in children.iter().enumerate() {
+                    child.fmt_indented(f, "", i + 1 == children.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseTree;
+    use crate::Production;
+
+    fn node(non_terminal: &str, derivation: Vec<&str>, children: Vec<ParseTree>) -> ParseTree {
+        ParseTree::Node {
+            production: Production::new(non_terminal, derivation),
+            children,
+        }
+    }
+
+    fn leaf(terminal: &str) -> ParseTree {
+        ParseTree::Leaf(terminal.to_string())
+    }
+
+    #[test]
+    fn collapses_unit_chains() {
+        let c = node("C", vec!["c"], vec![leaf("c")]);
+        let tree = node("A", vec!["B"], vec![node("B", vec!["C"], vec![c.clone()])]);
+        assert_eq!(tree.collapse_units(), c);
+    }
+
+    #[test]
+    fn keeps_nodes_with_a_single_child_left() {
+        let b = node("B", vec!["b"], vec![leaf("b")]);
+        let tree = node("A", vec!["B", "C"], vec![b]);
+        assert_eq!(tree.clone().collapse_units(), tree);
+    }
+
+    #[test]
+    fn keeps_a_production_of_a_single_terminal() {
+        let tree = node("A", vec!["a"], vec![leaf("a")]);
+        assert_eq!(tree.clone().collapse_units(), tree);
+    }
+}
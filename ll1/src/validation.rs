@@ -1,97 +1,251 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
+use super::error::{Diagnostic, GrammarError};
+use super::grammar::SymbolMode;
+use super::table::ConflictReport;
 use super::{Grammar, ParsingTable, Production};
 
 impl Grammar {
-    /// Validates if the grammar is LL(1) using both FIRST/FOLLOW sets and Parsing Table.
-    pub fn is_ll1(&self) -> bool {
-        if self.is_ll1_first_follow() && self.is_ll1_parsing_table() {
-            true
-        } else {
-            false
+    /// Checks that the grammar is well formed before it is analysed.
+    ///
+    /// Reports, in production order:
+    /// - a start symbol without productions (error)
+    /// - non-terminals used but never defined, under `%symbols case` (error);
+    ///   otherwise a symbol without rules is a terminal
+    /// - alternatives written twice for the same non-terminal (warning)
+    /// - ε written next to other symbols, as in `A -> ε b` (warning)
+    ///
+    /// followed by the cycles `A ⇒+ A` of a cyclic grammar (error).
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let defined: HashSet<&String> = self
+            .productions
+            .iter()
+            .map(|production| &production.non_terminal)
+            .collect();
+
+        if !defined.contains(&self.start_symbol) {
+            diagnostics.push(Diagnostic::error(
+                format!("Start symbol '{}' has no productions", self.start_symbol),
+                None,
+            ));
         }
-    }
 
-    /// Check if it is LL(1) using the ParseTable
-    /// The parse table is built then
-    /// if there are conflicts    -> it is not LL(1)
-    /// if there are no conflicts -> it is LL(1)
-    pub fn is_ll1_parsing_table(&self) -> bool {
-        match ParsingTable::build(self) {
-            Ok(_table) => true,
-            Err(_e) => false,
+        let mut reported: HashSet<&String> = HashSet::new();
+        let mut seen: HashSet<&Production> = HashSet::new();
+        for production in &self.productions {
+            for symbol in &production.derivation {
+                if self.symbol_mode == SymbolMode::Case
+                    && self.non_terminals.contains(symbol)
+                    && !defined.contains(symbol)
+                    && reported.insert(symbol)
+                {
+                    diagnostics.push(Diagnostic::error(
+                        format!(
+                            "Non-terminal '{}' is used by '{}' but has no productions",
+                            symbol, production.non_terminal
+                        ),
+                        production.span,
+                    ));
+                }
+            }
+
+            if !seen.insert(production) {
+                diagnostics.push(Diagnostic::warning(
+                    format!(
+                        "Duplicate alternative '{}' for '{}'",
+                        production.derivation.join(" "),
+                        production.non_terminal
+                    ),
+                    production.span,
+                ));
+            }
+
+            if production.derivation.len() > 1 && production.derivation.iter().any(|s| s == "ε") {
+                diagnostics.push(Diagnostic::warning(
+                    format!(
+                        "'ε' mixed with other symbols in '{}'; it derives nothing and can be dropped",
+                        production.derivation.join(" ")
+                    ),
+                    production.span,
+                ));
+            }
+        }
+
+        for cycle in self.cycles() {
+            let error = GrammarError::CyclicGrammar { cycle };
+            diagnostics.push(Diagnostic::error(error.to_string(), None));
         }
+
+        diagnostics
     }
 
-    // Pseudocode for is_ll1 using only first follow sets without Parsing Table
-    /*
-        For each non-terminal (NT):
-            For every pair of productions (P1, P2):
-                If FIRST(P1) ∩ FIRST(P2) ≠ ∅:
-                    Return false
-                If ε ∈ FIRST(P1) and FIRST(P2) ∩ FOLLOW(NT) ≠ ∅:
-                    Return false
-                If ε ∈ FIRST(P2) and FIRST(P1) ∩ FOLLOW(NT) ≠ ∅:
-                    Return false
-        Return true
-    */
+    /// Whether the grammar is LL(1), that is, whether its parsing table has
+    /// no conflicts
+    pub fn is_ll1(&self) -> bool {
+        self.conflict_report().is_empty()
+    }
+
+    /// Whether no two productions of a non-terminal have overlapping FIRST
+    /// sets and none that derives ε competes with FOLLOW; the same as
+    /// `is_ll1`
     pub fn is_ll1_first_follow(&self) -> bool {
-        let first_sets = self.compute_first_sets();
-        let follow_sets = self.compute_follow_sets(&first_sets);
+        self.conflict_report().is_empty()
+    }
 
-        // Group productions by their non-terminal symbols.
-        let mut productions_by_nt: HashMap<String, Vec<&Production>> = HashMap::new();
-        for production in &self.productions {
-            productions_by_nt
-                .entry(production.non_terminal.clone())
-                .or_insert_with(Vec::new)
-                .push(production);
-        }
+    /// Whether the parsing table builds without conflicts; the same as
+    /// `is_ll1`
+    pub fn is_ll1_parsing_table(&self) -> bool {
+        self.conflict_report().is_empty()
+    }
 
-        // Iterate through each non-terminal and its associated productions.
-        for (nt, productions) in &productions_by_nt {
-            // Compare every pair of productions for the same non-terminal.
-            for i in 0..productions.len() {
-                let first_i = self.compute_first_of_string(&productions[i].derivation, &first_sets);
-
-                for j in (i + 1)..productions.len() {
-                    let first_j =
-                        self.compute_first_of_string(&productions[j].derivation, &first_sets);
-
-                    // --- Rule 1: FIRST sets must not overlap ---
-                    // Ensure that the FIRST sets of two different productions are disjoint.
-                    // If they overlap, the grammar is ambiguous and not LL(1).
-                    if !first_i.is_disjoint(&first_j) {
-                        return false;
-                    }
-
-                    // --- Rule 2: Handling ε (Empty String) ---
-                    // If the FIRST set of one production contains ε:
-                    // Ensure that the FOLLOW set of the non-terminal does not overlap with
-                    // the FIRST set of the other production.
-
-                    // Check if ε is in FIRST(i) and FOLLOW(NT) ∩ FIRST(j) ≠ ∅
-                    if first_i.contains("ε") {
-                        if let Some(follow) = follow_sets.get(nt) {
-                            if !first_j.is_disjoint(follow) {
-                                return false;
-                            }
-                        }
-                    }
-
-                    // Check if ε is in FIRST(j) and FOLLOW(NT) ∩ FIRST(i) ≠ ∅
-                    if first_j.contains("ε") {
-                        if let Some(follow) = follow_sets.get(nt) {
-                            if !first_i.is_disjoint(follow) {
-                                return false;
-                            }
-                        }
-                    }
-                }
-            }
+    /// Every LL(1) conflict of the grammar: each parsing table cell claimed
+    /// by more than one production, with the competing productions and
+    /// whether it is a FIRST/FIRST or a FIRST/FOLLOW conflict
+    pub fn conflict_report(&self) -> ConflictReport {
+        ParsingTable::conflict_report(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::table::ConflictKind;
+    use crate::Grammar;
+
+    /// The cell, kind and competing derivations of each conflict
+    fn conflicts(source: &str) -> Vec<(String, String, ConflictKind, Vec<String>)> {
+        let grammar = Grammar::from_string(source, "S").unwrap();
+        grammar
+            .conflict_report()
+            .conflicts
+            .into_iter()
+            .map(|conflict| {
+                let derivations = conflict
+                    .productions
+                    .iter()
+                    .map(|production| production.derivation.join(" "))
+                    .collect();
+                (
+                    conflict.non_terminal,
+                    conflict.terminal,
+                    conflict.kind,
+                    derivations,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_first_first_conflicts() {
+        assert_eq!(
+            conflicts("S -> a b | a c\n"),
+            [(
+                "S".to_string(),
+                "a".to_string(),
+                ConflictKind::FirstFirst,
+                vec!["a b".to_string(), "a c".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_first_follow_conflicts() {
+        assert_eq!(
+            conflicts("S -> A a\nA -> a | ε\n"),
+            [(
+                "A".to_string(),
+                "a".to_string(),
+                ConflictKind::FirstFollow,
+                vec!["a".to_string(), "ε".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn every_ll1_check_agrees() {
+        for (source, ll1) in [
+            ("S -> a b | a c\n", false),
+            ("S -> A a\nA -> a | ε\n", false),
+            ("S -> a S | b\n", true),
+        ] {
+            let grammar = Grammar::from_string(source, "S").unwrap();
+            assert_eq!(grammar.is_ll1(), ll1);
+            assert_eq!(grammar.is_ll1_first_follow(), ll1);
+            assert_eq!(grammar.is_ll1_parsing_table(), ll1);
         }
+    }
+
+    /// Whether each diagnostic is an error, with its message and line
+    fn diagnostics(grammar: &Grammar) -> Vec<(bool, String, Option<usize>)> {
+        grammar
+            .validate()
+            .into_iter()
+            .map(|d| (d.is_error(), d.message, d.span.map(|span| span.line)))
+            .collect()
+    }
+
+    #[test]
+    fn reports_a_start_symbol_without_productions() {
+        let mut grammar = Grammar::new("S");
+        grammar.add_production("A", vec!["a"]);
+        assert_eq!(
+            diagnostics(&grammar),
+            [(
+                true,
+                "Start symbol 'S' has no productions".to_string(),
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_undefined_non_terminals_in_case_mode_only() {
+        let source = "S -> a B\n";
+        let case = Grammar::from_string(&format!("%symbols case\n{}", source), "S").unwrap();
+        assert_eq!(
+            diagnostics(&case),
+            [(
+                true,
+                "Non-terminal 'B' is used by 'S' but has no productions".to_string(),
+                Some(2)
+            )]
+        );
+        let declared = Grammar::from_string(source, "S").unwrap();
+        assert!(diagnostics(&declared).is_empty());
+    }
+
+    #[test]
+    fn warns_about_duplicate_alternatives_and_stray_epsilons() {
+        let grammar = Grammar::from_string("S -> a | b\nS -> a\nS -> ε c\n", "S").unwrap();
+        assert_eq!(
+            diagnostics(&grammar),
+            [
+                (
+                    false,
+                    "Duplicate alternative 'a' for 'S'".to_string(),
+                    Some(2)
+                ),
+                (
+                    false,
+                    "'ε' mixed with other symbols in 'ε c'; it derives nothing and can be dropped"
+                        .to_string(),
+                    Some(3)
+                ),
+            ]
+        );
+    }
 
-        // If no violations of LL(1) conditions were detected, return true.
-        true
+    #[test]
+    fn reports_cycles() {
+        let grammar = Grammar::from_string("S -> A | a\nA -> S\n", "S").unwrap();
+        let diagnostics = diagnostics(&grammar);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].0);
+        assert!(
+            diagnostics[0].1.contains("S → A → S"),
+            "{}",
+            diagnostics[0].1
+        );
     }
 }